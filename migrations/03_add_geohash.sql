@@ -0,0 +1,2 @@
+ALTER TABLE gpslog ADD COLUMN geohash VARCHAR(12);
+CREATE INDEX gpslog_geohash_idx ON gpslog (geohash);
@@ -0,0 +1,8 @@
+CREATE TABLE region_visits(
+    device_id INTEGER NOT NULL,
+    region VARCHAR(200) NOT NULL,
+    date VARCHAR(10) NOT NULL,
+    first_ts TIMESTAMPTZ NOT NULL,
+    last_ts TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (device_id, region, date)
+);
@@ -0,0 +1,4 @@
+CREATE TABLE region_visits_state(
+    boundaries VARCHAR(500) NOT NULL,
+    last_id INTEGER NOT NULL
+);
@@ -0,0 +1 @@
+CREATE INDEX gpslog_device_ts_idx ON gpslog (device_id, ts);
@@ -0,0 +1,97 @@
+use crate::db::TrackData;
+use crate::geojson::MAX_ACCURACY;
+use geo::{Distance, Haversine, Point};
+use serde::Deserialize;
+
+/// Sport of an exported activity
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ActivityType {
+    Running,
+    Cycling,
+    Walking,
+    Hiking,
+    #[default]
+    Other,
+}
+
+/// Track point of an activity
+#[derive(Debug)]
+pub struct Sample {
+    /// Seconds since epoch
+    pub ts: i64,
+    pub x: f64,
+    pub y: f64,
+    /// Altitude in meters
+    pub altitude: Option<f64>,
+    /// Distance from start in meters
+    pub distance: f64,
+    /// Speed in m/s
+    pub speed: Option<f64>,
+}
+
+/// Track points with timestamp and cumulated distance
+pub fn samples(track: &TrackData) -> Vec<Sample> {
+    let mut samples: Vec<Sample> = Vec::with_capacity(track.points.len());
+    // keep only points within 200 meters accuracy
+    for point in track
+        .points
+        .iter()
+        .filter(|point| point.accuracy.unwrap_or(0) < MAX_ACCURACY)
+    {
+        let Some(ts) = point.epoch() else {
+            continue;
+        };
+        let distance = samples.last().map_or(0.0, |last| {
+            last.distance
+                + Haversine::distance(Point::new(last.x, last.y), Point::new(point.x, point.y))
+        });
+        samples.push(Sample {
+            ts,
+            x: point.x,
+            y: point.y,
+            altitude: point.elevation.map(f64::from),
+            distance,
+            speed: point.speed.map(|val| val as f64 / 3.6),
+        });
+    }
+    samples
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::GpsPoint;
+
+    #[test]
+    fn sample_distances() {
+        let point = |x: f64, ts: &str, accuracy: Option<i32>| GpsPoint {
+            x,
+            y: 0.0,
+            ts: ts.to_string(),
+            accuracy,
+            speed: Some(36),
+            ..Default::default()
+        };
+        let track = TrackData {
+            device_id: 1,
+            date: "2025-10-09".to_string(),
+            points: vec![
+                point(0.0, "2025-10-09 08:00:00", Some(10)),
+                point(5.0, "2025-10-09 08:01:00", Some(MAX_ACCURACY)),
+                point(0.009, "2025-10-09 08:02:00", None),
+                point(7.0, "invalid", None),
+                point(0.018, "2025-10-09 08:04:00", Some(MAX_ACCURACY - 1)),
+            ],
+        };
+        let samples = samples(&track);
+        let xs: Vec<_> = samples.iter().map(|sample| sample.x).collect();
+        assert_eq!(xs, [0.0, 0.009, 0.018]);
+        assert_eq!(samples[0].distance, 0.0);
+        // 0.009° at the equator is about 1 km
+        assert!((samples[1].distance - 1000.8).abs() < 0.1);
+        assert!((samples[2].distance - 2001.5).abs() < 0.1);
+        assert_eq!(samples[2].ts - samples[0].ts, 240);
+        assert_eq!(samples[0].speed, Some(10.0));
+    }
+}
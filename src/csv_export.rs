@@ -0,0 +1,117 @@
+use crate::db::{Db, GpsPoint};
+use crate::export::{self, Column, ExportSelection};
+use futures_util::stream::BoxStream;
+use futures_util::StreamExt;
+use serde_json::Value;
+use tokio::sync::mpsc;
+
+const DEFAULT_COLUMNS: &str = "ts,lat,lon,tid,speed,elevation,accuracy,v_accuracy,cog,annotations";
+/// Number of rows sent as one chunk
+const CHUNK_SIZE: usize = 10000;
+
+fn new_writer() -> csv::Writer<Vec<u8>> {
+    csv::WriterBuilder::new().from_writer(Vec::new())
+}
+
+/// Text of a CSV field, empty for null values
+fn field(value: Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s,
+        value => value.to_string(),
+    }
+}
+
+/// Stream fixes as CSV with a header row
+pub async fn write(
+    db: &Db,
+    selection: &ExportSelection,
+    tx: mpsc::Sender<Vec<u8>>,
+) -> anyhow::Result<()> {
+    let columns = selection.columns(DEFAULT_COLUMNS)?;
+    let fixes = db.stream_fixes(
+        selection.device_id,
+        selection.date_from.as_deref(),
+        selection.date_to.as_deref(),
+    );
+    write_fixes(&columns, fixes, tx).await
+}
+
+/// Write fixes as CSV with a header row in chunks to a channel
+async fn write_fixes(
+    columns: &[Column],
+    mut fixes: BoxStream<'_, anyhow::Result<GpsPoint>>,
+    tx: mpsc::Sender<Vec<u8>>,
+) -> anyhow::Result<()> {
+    let mut writer = new_writer();
+    writer.write_record(columns.iter().map(|column| column.name()))?;
+    let mut rows = 0;
+    while let Some(pt) = fixes.next().await {
+        let pt = pt?;
+        let annotations = export::annotations(&pt);
+        writer.write_record(
+            columns
+                .iter()
+                .map(|column| field(column.value(&pt, &annotations))),
+        )?;
+        rows += 1;
+        if rows % CHUNK_SIZE == 0 {
+            let chunk = std::mem::replace(&mut writer, new_writer()).into_inner()?;
+            tx.send(chunk).await?;
+        }
+    }
+    tx.send(writer.into_inner()?).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::stream;
+
+    async fn export(columns: &str, points: Vec<GpsPoint>) -> String {
+        let selection = ExportSelection {
+            device_id: 1,
+            date_from: None,
+            date_to: None,
+            columns: Some(columns.to_string()),
+        };
+        let columns = selection.columns(DEFAULT_COLUMNS).unwrap();
+        let (tx, mut rx) = mpsc::channel(4);
+        let fixes = stream::iter(points.into_iter().map(Ok)).boxed();
+        write_fixes(&columns, fixes, tx).await.unwrap();
+        let mut csv = Vec::new();
+        while let Some(chunk) = rx.recv().await {
+            csv.extend(chunk);
+        }
+        String::from_utf8(csv).unwrap()
+    }
+
+    #[tokio::test]
+    async fn header_and_nulls() {
+        let points = vec![
+            GpsPoint {
+                x: 9.43,
+                y: 47.05,
+                ts: "2025-10-09 08:00:00".to_string(),
+                tid: "me".to_string(),
+                speed: Some(5),
+                annotations: r#"{"batt":80,"conn":"w"}"#.to_string(),
+                ..Default::default()
+            },
+            GpsPoint {
+                x: 9.44,
+                y: 47.06,
+                ts: "2025-10-09 08:01:00".to_string(),
+                tid: "me".to_string(),
+                annotations: "{}".to_string(),
+                ..Default::default()
+            },
+        ];
+        let csv = export("lat,lon,speed,accuracy,annotations.batt", points).await;
+        assert_eq!(
+            csv,
+            "lat,lon,speed,accuracy,batt\n47.05,9.43,5,,80\n47.06,9.44,,,\n"
+        );
+    }
+}
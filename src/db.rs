@@ -1,8 +1,23 @@
+#[cfg(feature = "duckdb")]
+use crate::duckdb_db::DuckDb;
+use crate::geojson::MAX_ACCURACY;
 use crate::owntracks::Location;
+use crate::regions::Boundaries;
+use crate::simplify::Simplification;
+use crate::smoothing::Smoothing;
+use crate::spatial_index;
+use crate::stationary::{PendingStay, StationaryConfig};
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, SecondsFormat};
+use futures_util::stream::{self, BoxStream};
+use futures_util::{StreamExt, TryStreamExt};
 use serde::{ser::Error, Deserialize, Deserializer, Serialize, Serializer};
 use serde_json::Value;
+use sqlx::any::AnyArguments;
 use sqlx::migrate::{MigrateDatabase, Migrator};
-use sqlx::{AnyPool, Sqlite};
+use sqlx::query::{Query, QueryAs};
+use sqlx::{Any, AnyPool, Sqlite};
+use std::collections::HashMap;
+use std::sync::Arc;
 
 static MIGRATOR: Migrator = sqlx::migrate!();
 
@@ -13,6 +28,16 @@ pub struct TrackRef {
     pub ts_start: String,
     /// Query segmented track
     pub segmented: Option<bool>,
+    /// Collapse stationary clusters into single points
+    pub collapse: Option<bool>,
+    /// Smooth track points
+    pub smooth: Option<Smoothing>,
+    /// Simplify track line
+    pub simplify: Option<Simplification>,
+    /// Simplification tolerance in meters
+    pub tolerance: Option<f64>,
+    /// Zoom level for deriving the simplification tolerance
+    pub zoom: Option<u8>,
 }
 
 #[derive(sqlx::FromRow, Debug)]
@@ -22,7 +47,7 @@ pub struct TrackData {
     pub points: Vec<GpsPoint>,
 }
 
-#[derive(sqlx::FromRow, Debug)]
+#[derive(sqlx::FromRow, Clone, Debug, Default)]
 pub struct GpsPoint {
     pub y: f64,
     pub x: f64,
@@ -37,6 +62,17 @@ pub struct GpsPoint {
     pub v_accuracy: Option<i16>,
     pub cog: Option<i16>,
     pub annotations: String,
+    /// Elevation from digital elevation model
+    #[sqlx(skip)]
+    pub dem_elevation: bool,
+}
+
+/// Fix of a device
+#[derive(sqlx::FromRow, Debug)]
+struct DeviceFix {
+    device_id: i32,
+    #[sqlx(flatten)]
+    point: GpsPoint,
 }
 
 #[derive(sqlx::FromRow, Debug)]
@@ -56,6 +92,65 @@ pub struct Position {
     pub cog: Option<i16>,
 }
 
+/// Fix of a device within an area
+#[derive(sqlx::FromRow, Debug)]
+pub struct AreaFix {
+    pub device_id: i32,
+    pub y: f64,
+    pub x: f64,
+    /// Timestamp in format 2025-02-19 06:46:54+00
+    pub ts: String,
+    /// Date in format 2025-02-19
+    pub date: String,
+}
+
+/// First and last fix of a device within an area on a day
+#[derive(sqlx::FromRow, Debug, PartialEq)]
+pub struct AreaPassage {
+    pub device_id: i32,
+    /// Date in format 2025-02-19
+    pub date: String,
+    /// Timestamp of first fix in format 2025-02-19 06:46:54+00
+    pub ts_enter: String,
+    /// Timestamp of last fix
+    pub ts_leave: String,
+}
+
+/// Fix of a track crossing a bounding box
+#[derive(sqlx::FromRow, Debug)]
+struct AreaTrackFix {
+    device_id: i32,
+    date: String,
+    /// Position within the fixes of the day
+    seq: i64,
+    #[sqlx(flatten)]
+    point: GpsPoint,
+}
+
+/// Track of a device and day crossing a bounding box
+#[derive(Debug)]
+pub struct AreaTrack {
+    pub device_id: i32,
+    /// Date in format 2025-02-19
+    pub date: String,
+    /// Runs of consecutive fixes within the bounding box, with their outer neighbours
+    pub runs: Vec<Vec<GpsPoint>>,
+}
+
+/// Filter for fixes within a bounding box
+#[derive(Debug, Default)]
+pub struct AreaFilter {
+    /// xmin, ymin, xmax, ymax
+    pub bbox: [f64; 4],
+    /// First date in format 2025-02-19
+    pub date_from: Option<String>,
+    /// Last date in format 2025-02-19
+    pub date_to: Option<String>,
+    pub device_id: Option<i32>,
+    /// GeoJSON geometry of the search area (PostGIS mode only)
+    pub area: Option<String>,
+}
+
 #[derive(sqlx::FromRow, Serialize, Debug)]
 pub struct TrackInfo {
     pub device_id: i32,
@@ -64,6 +159,52 @@ pub struct TrackInfo {
     pub tid: String,
     pub ts_start: String, // DateTime<FixedOffset> is not supported by Any driver
     pub ts_end: String,   // DateTime<FixedOffset> is not supported by Any driver
+    /// Track length in meters (PostGIS mode only)
+    #[sqlx(skip)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub distance: Option<f64>,
+    /// Bounding box xmin, ymin, xmax, ymax (PostGIS mode only)
+    #[sqlx(skip)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bbox: Option<[f64; 4]>,
+}
+
+/// Track info with its date
+#[derive(sqlx::FromRow, Debug)]
+struct DateTrackInfo {
+    date: String,
+    #[sqlx(flatten)]
+    track: TrackInfo,
+}
+
+/// Track length and extent computed by PostGIS
+///
+/// Values are NULL on days without valid geometry.
+#[derive(sqlx::FromRow, Debug)]
+struct TrackExtent {
+    device_id: i32,
+    date: String,
+    distance: Option<f64>,
+    xmin: Option<f64>,
+    ymin: Option<f64>,
+    xmax: Option<f64>,
+    ymax: Option<f64>,
+}
+
+impl TrackExtent {
+    fn bbox(&self) -> Option<[f64; 4]> {
+        Some([self.xmin?, self.ymin?, self.xmax?, self.ymax?])
+    }
+}
+
+#[derive(sqlx::FromRow, Serialize, Debug)]
+pub struct RegionVisit {
+    pub device_id: i32,
+    pub region: String,
+    /// Number of days with fixes in region
+    pub days: i64,
+    pub first_visit: String, // DateTime<FixedOffset> is not supported by Any driver
+    pub last_visit: String,  // DateTime<FixedOffset> is not supported by Any driver
 }
 
 impl TrackRef {
@@ -71,17 +212,102 @@ impl TrackRef {
         // from timestamp in format 2025-02-19 06:46:54+00
         self.ts_start.split(' ').next().unwrap().to_string()
     }
+
+    /// Requested simplification algorithm, Douglas-Peucker if only a tolerance is given
+    pub fn simplification(&self) -> Option<Simplification> {
+        if self.tolerance.is_some() || self.zoom.is_some() {
+            Some(self.simplify.unwrap_or(Simplification::Dp))
+        } else {
+            self.simplify
+        }
+    }
+}
+
+impl GpsPoint {
+    /// Timestamp in seconds since epoch
+    pub fn epoch(&self) -> Option<i64> {
+        parse_timestamp(&self.ts).map(|dt| dt.timestamp())
+    }
+    /// Timestamp in format 2025-02-19T06:46:54Z
+    pub fn rfc3339(&self) -> Option<String> {
+        parse_timestamp(&self.ts).map(|dt| dt.to_utc().to_rfc3339_opts(SecondsFormat::Secs, true))
+    }
+}
+
+/// Start and end of a day in seconds since epoch (UTC)
+pub fn day_range(date: &str) -> Option<(i64, i64)> {
+    let day_start = NaiveDate::parse_from_str(date, "%F")
+        .ok()?
+        .and_hms_opt(0, 0, 0)?
+        .and_utc()
+        .timestamp();
+    Some((day_start, day_start + 24 * 3600))
+}
+
+/// Parse timestamp in format 2025-02-19 06:46:54+00 or 2025-02-19 06:46:54 (UTC)
+pub fn parse_timestamp(ts: &str) -> Option<DateTime<FixedOffset>> {
+    DateTime::<FixedOffset>::parse_from_str(ts, "%F %T%#z")
+        .or(NaiveDateTime::parse_from_str(ts, "%F %T").map(|utcts| utcts.and_utc().fixed_offset()))
+        .ok()
+}
+
+#[derive(Clone)]
+enum Backend {
+    /// SQLite or PostgreSQL
+    Sqlx(AnyPool),
+    #[cfg(feature = "duckdb")]
+    DuckDb(DuckDb),
+}
+
+/// Collapsing of stationary fixes at ingestion time
+struct IngestionCollapse {
+    cfg: StationaryConfig,
+    /// Stays in progress by device id
+    stays: tokio::sync::Mutex<HashMap<i64, PendingStay>>,
 }
 
 #[derive(Clone)]
 pub struct Db {
-    pool: AnyPool,
+    backend: Backend,
+    /// Store positions as PostGIS geographies and use ST_ functions
+    postgis: bool,
+    /// Boundaries for recording visited regions
+    boundaries: Option<Arc<Boundaries>>,
+    /// Collapse stationary fixes at ingestion time
+    collapse_stationary: Option<Arc<IngestionCollapse>>,
 }
 
 impl Db {
     pub async fn connect() -> anyhow::Result<Self> {
         let conn_str =
             dotenvy::var("DB_CONNECTION").unwrap_or("sqlite://tracking.sqlite".to_string());
+        if let Some(path) = conn_str.strip_prefix("duckdb://") {
+            #[cfg(feature = "duckdb")]
+            {
+                let unsupported = [
+                    ("BOUNDARY_FILE", dotenvy::var("BOUNDARY_FILE").is_ok()),
+                    (
+                        "DB_POSTGIS",
+                        dotenvy::var("DB_POSTGIS").is_ok_and(|s| s == "true"),
+                    ),
+                    (
+                        "COLLAPSE_STATIONARY",
+                        StationaryConfig::ingestion_from_env().is_some(),
+                    ),
+                ];
+                for (var, _) in unsupported.iter().filter(|(_, enabled)| *enabled) {
+                    log::warn!("{var} is not supported by DuckDB databases");
+                }
+                return Ok(Db {
+                    backend: Backend::DuckDb(DuckDb::open(path)?),
+                    postgis: false,
+                    boundaries: None,
+                    collapse_stationary: None,
+                });
+            }
+            #[cfg(not(feature = "duckdb"))]
+            anyhow::bail!("DuckDB database {path} requires building with the `duckdb` feature");
+        }
         if conn_str.starts_with("sqlite:")
             && !Sqlite::database_exists(&conn_str).await.unwrap_or(false)
         {
@@ -91,13 +317,50 @@ impl Db {
         sqlx::any::install_default_drivers();
         log::info!("Connecting to database...");
         let pool = AnyPool::connect(&conn_str).await?;
-        Ok(Db { pool })
+        let mut postgis = dotenvy::var("DB_POSTGIS")
+            .map(|s| s == "true")
+            .unwrap_or(false);
+        if postgis && pool.acquire().await?.backend_name() != "PostgreSQL" {
+            log::warn!("DB_POSTGIS requires a PostgreSQL database");
+            postgis = false;
+        }
+        let boundaries = Boundaries::from_env().await?.map(Arc::new);
+        let collapse_stationary = StationaryConfig::ingestion_from_env().map(|cfg| {
+            Arc::new(IngestionCollapse {
+                cfg,
+                stays: Default::default(),
+            })
+        });
+        Ok(Db {
+            backend: Backend::Sqlx(pool),
+            postgis,
+            boundaries,
+            collapse_stationary,
+        })
+    }
+
+    /// Connection pool of SQL backends
+    fn pool(&self) -> anyhow::Result<&AnyPool> {
+        match &self.backend {
+            Backend::Sqlx(pool) => Ok(pool),
+            #[cfg(feature = "duckdb")]
+            Backend::DuckDb(_) => anyhow::bail!("Operation not supported by DuckDB backend"),
+        }
+    }
+
+    /// PostGIS mode enabled
+    pub fn postgis(&self) -> bool {
+        self.postgis
     }
 
     pub async fn run_migrations(&self) -> anyhow::Result<()> {
         log::info!("Running database migrations...");
-        MIGRATOR.run(&self.pool).await?;
-        let is_pg = self.pool.acquire().await?.backend_name() == "PostgreSQL";
+        #[cfg(feature = "duckdb")]
+        if let Backend::DuckDb(duckdb) = &self.backend {
+            return duckdb.run_migrations().await;
+        }
+        MIGRATOR.run(self.pool()?).await?;
+        let is_pg = self.pool()?.acquire().await?.backend_name() == "PostgreSQL";
         if is_pg {
             let _result = sqlx::raw_sql(
                 r#"
@@ -120,9 +383,160 @@ impl Db {
                     IMMUTABLE;
                 "#,
             )
-            .execute(&self.pool)
+            .execute(self.pool()?)
             .await?;
         }
+        if self.postgis {
+            self.run_postgis_migrations().await?;
+        }
+        self.update_geohashes().await?;
+        Ok(())
+    }
+
+    /// Add geography columns maintained from lat/lon by triggers
+    async fn run_postgis_migrations(&self) -> anyhow::Result<()> {
+        log::info!("Setting up PostGIS geography columns...");
+        let _result = sqlx::raw_sql(
+            r#"
+            CREATE EXTENSION IF NOT EXISTS postgis;
+            CREATE OR REPLACE FUNCTION set_geog() RETURNS TRIGGER AS $$
+                BEGIN
+                    NEW.geog := ST_SetSRID(ST_MakePoint(NEW.lon, NEW.lat), 4326)::geography;
+                    RETURN NEW;
+                END;
+                $$ LANGUAGE plpgsql;
+            ALTER TABLE gpslog ADD COLUMN IF NOT EXISTS geog geography(Point, 4326);
+            CREATE INDEX IF NOT EXISTS gpslog_geog_idx ON gpslog USING GIST (geog);
+            DROP TRIGGER IF EXISTS gpslog_geog ON gpslog;
+            CREATE TRIGGER gpslog_geog BEFORE INSERT OR UPDATE OF lat, lon ON gpslog
+                FOR EACH ROW EXECUTE FUNCTION set_geog();
+            UPDATE gpslog SET geog = ST_SetSRID(ST_MakePoint(lon, lat), 4326)::geography
+                WHERE geog IS NULL;
+            ALTER TABLE devices ADD COLUMN IF NOT EXISTS geog geography(Point, 4326);
+            CREATE INDEX IF NOT EXISTS devices_geog_idx ON devices USING GIST (geog);
+            DROP TRIGGER IF EXISTS devices_geog ON devices;
+            CREATE TRIGGER devices_geog BEFORE INSERT OR UPDATE OF lat, lon ON devices
+                FOR EACH ROW EXECUTE FUNCTION set_geog();
+            UPDATE devices SET geog = ST_SetSRID(ST_MakePoint(lon, lat), 4326)::geography
+                WHERE geog IS NULL;
+            "#,
+        )
+        .execute(self.pool()?)
+        .await?;
+        Ok(())
+    }
+
+    /// Set missing geohashes of logged fixes
+    ///
+    /// Invalid positions get an empty geohash, like on insert, so only fixes logged
+    /// before the geohash migration are selected.
+    async fn update_geohashes(&self) -> anyhow::Result<()> {
+        const BATCH_SIZE: i64 = 1000;
+        loop {
+            let rows: Vec<(i64, f64, f64)> = sqlx::query_as(
+                "SELECT id, lat, lon FROM gpslog WHERE geohash IS NULL ORDER BY id LIMIT $1",
+            )
+            .bind(BATCH_SIZE)
+            .fetch_all(self.pool()?)
+            .await?;
+            if rows.is_empty() {
+                return Ok(());
+            }
+            log::info!("Updating geohashes of {} fixes...", rows.len());
+            // Single UPDATE per batch: SET geohash = CASE id WHEN .. THEN .. END
+            let cases = (0..rows.len())
+                .map(|i| format!("WHEN ${} THEN ${}", 2 * i + 1, 2 * i + 2))
+                .collect::<Vec<_>>()
+                .join(" ");
+            let ids = (0..rows.len())
+                .map(|i| format!("${}", 2 * rows.len() + i + 1))
+                .collect::<Vec<_>>()
+                .join(", ");
+            let sql =
+                format!("UPDATE gpslog SET geohash = CASE id {cases} END WHERE id IN ({ids})");
+            let mut query = sqlx::query(&sql);
+            for (id, lat, lon) in &rows {
+                query = query
+                    .bind(id)
+                    .bind(spatial_index::encode(*lon, *lat).unwrap_or_default());
+            }
+            for (id, _, _) in &rows {
+                query = query.bind(id);
+            }
+            query.execute(self.pool()?).await?;
+        }
+    }
+
+    /// Record visited regions of fixes logged since the last run.
+    ///
+    /// All visits are recomputed when the boundary source changed.
+    pub async fn update_region_visits(&self) -> anyhow::Result<()> {
+        let Some(boundaries) = &self.boundaries else {
+            return Ok(());
+        };
+        let mut tx = self.pool()?.begin().await?;
+        let state: Option<(String, i64)> =
+            sqlx::query_as("SELECT boundaries, last_id FROM region_visits_state")
+                .fetch_optional(&mut *tx)
+                .await?;
+        let last_id = match state {
+            Some((source, last_id)) if source == boundaries.source() => last_id,
+            _ => {
+                sqlx::query("DELETE FROM region_visits")
+                    .execute(&mut *tx)
+                    .await?;
+                0
+            }
+        };
+        let max_id: Option<i64> = sqlx::query_scalar("SELECT MAX(id) FROM gpslog")
+            .fetch_one(&mut *tx)
+            .await?;
+        let max_id = max_id.unwrap_or(0).max(last_id);
+        if max_id > last_id {
+            log::info!("Computing visited regions...");
+        }
+        // First and last visit per device, region and day
+        let mut visits: HashMap<(i64, String, String), (i64, i64)> = HashMap::new();
+        let mut fixes = sqlx::query_as::<_, (i64, f64, f64, String)>(
+            "SELECT device_id, lat, lon, datetime(ts, 'unixepoch') FROM gpslog WHERE id > $1 AND id <= $2",
+        )
+        .bind(last_id)
+        .bind(max_id)
+        .fetch(&mut *tx);
+        while let Some((device_id, lat, lon, ts)) = fixes.try_next().await? {
+            let Some(ts) = parse_timestamp(&ts) else {
+                continue;
+            };
+            let date = ts.format("%F").to_string();
+            let ts = ts.timestamp();
+            for region in boundaries.regions_at(lon, lat) {
+                visits
+                    .entry((device_id, region.to_string(), date.clone()))
+                    .and_modify(|(first, last)| {
+                        *first = (*first).min(ts);
+                        *last = (*last).max(ts);
+                    })
+                    .or_insert((ts, ts));
+            }
+        }
+        drop(fixes);
+        for ((device_id, region, date), (first, last)) in &visits {
+            region_visit_query(*device_id, region, date, *first, *last)
+                .execute(&mut *tx)
+                .await?;
+        }
+        sqlx::query("DELETE FROM region_visits_state")
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("INSERT INTO region_visits_state (boundaries, last_id) VALUES ($1, $2)")
+            .bind(boundaries.source())
+            .bind(max_id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        if max_id > last_id {
+            log::info!("{} region visits recorded", visits.len());
+        }
         Ok(())
     }
 
@@ -132,6 +546,11 @@ impl Db {
         device: &str,
         loc: &Location,
     ) -> anyhow::Result<()> {
+        #[cfg(feature = "duckdb")]
+        if let Backend::DuckDb(duckdb) = &self.backend {
+            return duckdb.insert_location(user, device, loc).await;
+        }
+        let mut tx = self.pool()?.begin().await?;
         // Upsert device location
         let device_id: i64 = sqlx::query_scalar(r#"
             INSERT INTO devices (user_id, device, tid, ts, velocity, lat, lon, alt, accuracy, v_accuracy, cog)
@@ -151,13 +570,75 @@ impl Db {
         .bind(loc.accuracy.map(|val| val as i64)) // u32 is not supported by Any driver
         .bind(loc.v_accuracy)
         .bind(loc.cog)
-        .fetch_one(&self.pool)
+        .fetch_one(&mut *tx)
         .await?;
 
-        sqlx::query(
+        // Record visited regions of the logged fix
+        if let Some(boundaries) = &self.boundaries {
+            let date = DateTime::from_timestamp(loc.ts, 0)
+                .unwrap_or_default()
+                .format("%F")
+                .to_string();
+            for region in boundaries.regions_at(loc.lon as f64, loc.lat as f64) {
+                region_visit_query(device_id, region, &date, loc.ts, loc.ts)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+        }
+
+        let Some(collapse) = &self.collapse_stationary else {
+            self.insert_fix(&mut tx, device_id, loc).await?;
+            tx.commit().await?;
+            return Ok(());
+        };
+        let position = (loc.lon as f64, loc.lat as f64);
+        let speed = loc.velocity.map(|val| val as i16);
+        let accuracy = loc.accuracy.map(|val| val as i32);
+        let mut stays = collapse.stays.lock().await;
+        if let Some(stay) = stays.get_mut(&device_id) {
+            if stay.add(position, loc.ts, speed, accuracy, &collapse.cfg) {
+                if stay.is_complete(&collapse.cfg) {
+                    Self::merge_stay(&mut tx, stay).await?;
+                } else {
+                    let id = self.insert_fix(&mut tx, device_id, loc).await?;
+                    stay.merged_ids.push(id);
+                }
+                tx.commit().await?;
+                return Ok(());
+            }
+        }
+        // Device moved or no stay in progress
+        let id = self.insert_fix(&mut tx, device_id, loc).await?;
+        let stay = PendingStay::start(
+            id,
+            position,
+            loc.ts,
+            speed,
+            accuracy,
+            &loc.annotations,
+            &collapse.cfg,
+        );
+        match stay {
+            Some(stay) => stays.insert(device_id, stay),
+            None => stays.remove(&device_id),
+        };
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// Log a fix, returning its id
+    async fn insert_fix(
+        &self,
+        tx: &mut sqlx::Transaction<'_, Any>,
+        device_id: i64,
+        loc: &Location,
+    ) -> anyhow::Result<i64> {
+        let id: i64 = sqlx::query_scalar(
             r#"INSERT INTO gpslog
-             (device_id, tid, ts, velocity, lat, lon, alt, accuracy, v_accuracy, cog, annotations)
-              VALUES ($1, $2, unixepoch($3, 'unixepoch'), $4, $5, $6, $7, $8, $9, $10, $11)"#,
+             (device_id, tid, ts, velocity, lat, lon, alt, accuracy, v_accuracy, cog, annotations, geohash)
+              VALUES ($1, $2, unixepoch($3, 'unixepoch'), $4, $5, $6, $7, $8, $9, $10, $11, $12)
+              RETURNING id"#,
         )
         .bind(device_id)
         .bind(&loc.tid)
@@ -170,16 +651,72 @@ impl Db {
         .bind(loc.v_accuracy)
         .bind(loc.cog)
         .bind(&loc.annotations)
-        .execute(&self.pool)
+        .bind(spatial_index::encode(loc.lon as f64, loc.lat as f64).unwrap_or_default())
+        .fetch_one(&mut **tx)
         .await?;
+        Ok(id)
+    }
 
+    /// Merge the logged fixes of a stay into its first fix
+    async fn merge_stay(
+        tx: &mut sqlx::Transaction<'_, Any>,
+        stay: &mut PendingStay,
+    ) -> anyhow::Result<()> {
+        for id in stay.merged_ids.drain(..) {
+            sqlx::query("DELETE FROM gpslog WHERE id = $1")
+                .bind(id)
+                .execute(&mut **tx)
+                .await?;
+        }
+        let (x, y) = stay.center();
+        sqlx::query(
+            "UPDATE gpslog SET lat = $1, lon = $2, accuracy = $3, annotations = $4, geohash = $5 WHERE id = $6",
+        )
+        .bind(y)
+        .bind(x)
+        .bind(stay.accuracy())
+        .bind(stay.annotations())
+        .bind(spatial_index::encode(x, y).unwrap_or_default())
+        .bind(stay.anchor_id)
+        .execute(&mut **tx)
+        .await?;
         Ok(())
     }
 
     /// Return track infos of a given date
     pub async fn query_tracks_info(&self, date: &str) -> anyhow::Result<Vec<TrackInfo>> {
-        let mut tracks: Vec<TrackInfo> = sqlx::query_as(
+        #[cfg(feature = "duckdb")]
+        if let Backend::DuckDb(duckdb) = &self.backend {
+            return duckdb.query_tracks_info(date).await;
+        }
+        let mut tracks: Vec<TrackInfo> = self
+            .query_dates_tracks_info(&[date])
+            .await?
+            .into_iter()
+            .map(|(_, track)| track)
+            .collect();
+
+        // Sort the tracks by ts_end in descending order
+        tracks.sort_by(|a, b| b.ts_end.cmp(&a.ts_end));
+
+        Ok(tracks)
+    }
+
+    /// Return track infos of multiple dates together with their date
+    pub async fn query_dates_tracks_info(
+        &self,
+        dates: &[&str],
+    ) -> anyhow::Result<Vec<(String, TrackInfo)>> {
+        if dates.is_empty() {
+            return Ok(Vec::new());
+        }
+        let params = (1..=dates.len())
+            .map(|no| format!("${no}"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let sql = format!(
             r#"SELECT
+                date(gpslog.ts, 'unixepoch') AS date,
                 device_id,
                 user_id,
                 device,
@@ -188,21 +725,65 @@ impl Db {
                 datetime(max(gpslog.ts), 'unixepoch') as ts_end
             FROM gpslog
             JOIN devices ON gpslog.device_id = devices.id
-            WHERE date(gpslog.ts, 'unixepoch') = $1
-            GROUP BY device_id, user_id, device, devices.tid"#,
-        )
-        .bind(date)
-        .fetch_all(&self.pool)
-        .await?;
+            WHERE date(gpslog.ts, 'unixepoch') IN ({params})
+            GROUP BY date(gpslog.ts, 'unixepoch'), device_id, user_id, device, devices.tid"#
+        );
+        let mut query = sqlx::query_as(&sql);
+        for date in dates {
+            query = query.bind(*date);
+        }
+        let rows: Vec<DateTrackInfo> = query.fetch_all(self.pool()?).await?;
+        let mut tracks: Vec<(String, TrackInfo)> =
+            rows.into_iter().map(|row| (row.date, row.track)).collect();
 
-        // Sort the tracks by ts_end in descending order
-        tracks.sort_by(|a, b| b.ts_end.cmp(&a.ts_end));
+        if self.postgis {
+            let sql = format!(
+                r#"SELECT
+                    device_id,
+                    date,
+                    ST_Length(line::geography) AS distance,
+                    ST_XMin(extent) AS xmin,
+                    ST_YMin(extent) AS ymin,
+                    ST_XMax(extent) AS xmax,
+                    ST_YMax(extent) AS ymax
+                FROM (
+                    SELECT
+                        device_id,
+                        date(ts, 'unixepoch') AS date,
+                        ST_MakeLine(geog::geometry ORDER BY id) AS line,
+                        ST_Extent(geog::geometry)::box3d AS extent
+                    FROM gpslog
+                    WHERE date(ts, 'unixepoch') IN ({params})
+                    AND (accuracy IS NULL OR accuracy < {MAX_ACCURACY})
+                    GROUP BY device_id, date(ts, 'unixepoch')
+                ) AS tracks"#
+            );
+            let mut query = sqlx::query_as(&sql);
+            for date in dates {
+                query = query.bind(*date);
+            }
+            let extents: Vec<TrackExtent> = query.fetch_all(self.pool()?).await?;
+            let mut extents: HashMap<(i32, String), TrackExtent> = extents
+                .into_iter()
+                .map(|extent| ((extent.device_id, extent.date.clone()), extent))
+                .collect();
+            for (date, track) in &mut tracks {
+                if let Some(extent) = extents.remove(&(track.device_id, date.clone())) {
+                    track.distance = extent.distance;
+                    track.bbox = extent.bbox();
+                }
+            }
+        }
 
         Ok(tracks)
     }
 
     /// Query a single track
     pub async fn query_track(&self, track_ref: &TrackRef) -> anyhow::Result<TrackData> {
+        #[cfg(feature = "duckdb")]
+        if let Backend::DuckDb(duckdb) = &self.backend {
+            return duckdb.query_track(track_ref).await;
+        }
         let date = track_ref.date();
         let points: Vec<GpsPoint> = sqlx::query_as(
             r#"
@@ -225,7 +806,7 @@ impl Db {
         )
         .bind(&date)
         .bind(track_ref.device_id)
-        .fetch_all(&self.pool)
+        .fetch_all(self.pool()?)
         .await?;
 
         let track = TrackData {
@@ -237,8 +818,344 @@ impl Db {
         Ok(track)
     }
 
+    /// Query the length of a track in meters with ST_Length (PostGIS mode only)
+    ///
+    /// Returns `None` without PostGIS or if the track has no valid geometry.
+    pub async fn query_track_distance(&self, track_ref: &TrackRef) -> anyhow::Result<Option<f64>> {
+        if !self.postgis {
+            return Ok(None);
+        }
+        let sql = format!(
+            r#"
+                SELECT ST_Length(ST_MakeLine(geog::geometry ORDER BY id)::geography)
+                FROM gpslog
+                WHERE date(ts, 'unixepoch') = $1
+                AND device_id = $2
+                AND (accuracy IS NULL OR accuracy < {MAX_ACCURACY})
+                "#
+        );
+        let distance: Option<f64> = sqlx::query_scalar(&sql)
+            .bind(track_ref.date())
+            .bind(track_ref.device_id)
+            .fetch_one(self.pool()?)
+            .await?;
+        Ok(distance)
+    }
+
+    /// Query fixes of a device within a time range (seconds since epoch), ordered by time
+    pub async fn query_fixes(
+        &self,
+        device_id: i32,
+        ts_from: i64,
+        ts_to: i64,
+    ) -> anyhow::Result<Vec<GpsPoint>> {
+        let points: Vec<GpsPoint> = sqlx::query_as(
+            r#"
+                SELECT
+                    lat as y,
+                    lon as x,
+                    datetime(ts, 'unixepoch') AS ts,
+                    tid,
+                    velocity as speed,
+                    alt as elevation,
+                    accuracy,
+                    v_accuracy,
+                    cog,
+                    annotations
+                FROM gpslog
+                WHERE device_id = $1
+                AND ts >= unixepoch($2, 'unixepoch')
+                AND ts <= unixepoch($3, 'unixepoch')
+                ORDER BY ts, id
+                "#,
+        )
+        .bind(device_id)
+        .bind(ts_from)
+        .bind(ts_to)
+        .fetch_all(self.pool()?)
+        .await?;
+
+        Ok(points)
+    }
+
+    /// Query fixes of all devices within a time range (seconds since epoch), grouped by device
+    pub async fn query_device_fixes(
+        &self,
+        ts_from: i64,
+        ts_to: i64,
+    ) -> anyhow::Result<Vec<(i32, Vec<GpsPoint>)>> {
+        let fixes: Vec<DeviceFix> = sqlx::query_as(
+            r#"
+                SELECT
+                    device_id,
+                    lat as y,
+                    lon as x,
+                    datetime(ts, 'unixepoch') AS ts,
+                    tid,
+                    velocity as speed,
+                    alt as elevation,
+                    accuracy,
+                    v_accuracy,
+                    cog,
+                    annotations
+                FROM gpslog
+                WHERE ts >= unixepoch($1, 'unixepoch')
+                AND ts <= unixepoch($2, 'unixepoch')
+                ORDER BY device_id, ts, id
+                "#,
+        )
+        .bind(ts_from)
+        .bind(ts_to)
+        .fetch_all(self.pool()?)
+        .await?;
+
+        let mut devices: Vec<(i32, Vec<GpsPoint>)> = Vec::new();
+        for fix in fixes {
+            match devices.last_mut() {
+                Some((device_id, points)) if *device_id == fix.device_id => points.push(fix.point),
+                _ => devices.push((fix.device_id, vec![fix.point])),
+            }
+        }
+        Ok(devices)
+    }
+
+    /// Query fixes within a bounding box
+    pub async fn query_area_fixes(&self, filter: &AreaFilter) -> anyhow::Result<Vec<AreaFix>> {
+        let cover = self.area_cover(filter);
+        let sql = format!(
+            r#"
+            SELECT
+                device_id,
+                lat as y,
+                lon as x,
+                datetime(ts, 'unixepoch') AS ts,
+                date(ts, 'unixepoch') AS date
+            FROM gpslog
+            WHERE {}
+            ORDER BY device_id, ts"#,
+            area_conditions(filter, &cover, self.postgis)
+        );
+        let fixes: Vec<AreaFix> = bind_area_filter(sqlx::query_as(&sql), filter, &cover)
+            .fetch_all(self.pool()?)
+            .await?;
+
+        Ok(fixes)
+    }
+
+    /// Geohash ranges covering the bounding box of an area filter, PostGIS uses its own index
+    fn area_cover(&self, filter: &AreaFilter) -> Vec<(String, Option<String>)> {
+        if self.postgis {
+            Vec::new()
+        } else {
+            spatial_index::cover(filter.bbox).unwrap_or_default()
+        }
+    }
+
+    /// Query first and last fix within an area per device and day, most recent first
+    pub async fn query_area_passages(
+        &self,
+        filter: &AreaFilter,
+        limit: usize,
+    ) -> anyhow::Result<Vec<AreaPassage>> {
+        let cover = self.area_cover(filter);
+        let sql = format!(
+            r#"
+            SELECT
+                device_id,
+                date(ts, 'unixepoch') AS date,
+                datetime(MIN(ts), 'unixepoch') AS ts_enter,
+                datetime(MAX(ts), 'unixepoch') AS ts_leave
+            FROM gpslog
+            WHERE {}
+            GROUP BY device_id, date(ts, 'unixepoch')
+            ORDER BY MIN(ts) DESC, device_id
+            LIMIT {limit}"#,
+            area_conditions(filter, &cover, self.postgis)
+        );
+        let passages: Vec<AreaPassage> = bind_area_filter(sqlx::query_as(&sql), filter, &cover)
+            .fetch_all(self.pool()?)
+            .await?;
+
+        Ok(passages)
+    }
+
+    /// Query fixes of tracks crossing a bounding box, split into runs within the box.
+    ///
+    /// Each run contains consecutive fixes within the box plus the neighbouring fix
+    /// before and after, so that lines can be clipped at the box border.
+    pub async fn query_area_tracks(&self, filter: &AreaFilter) -> anyhow::Result<Vec<AreaTrack>> {
+        let cover = self.area_cover(filter);
+        let conditions = area_conditions(filter, &cover, self.postgis);
+        let sql = format!(
+            r#"
+            WITH tracks AS (
+                SELECT DISTINCT date(ts, 'unixepoch') AS date, device_id
+                FROM gpslog
+                WHERE {conditions}
+            ),
+            fixes AS (
+                SELECT
+                    device_id,
+                    date(ts, 'unixepoch') AS date,
+                    id,
+                    ts,
+                    lat,
+                    lon,
+                    tid,
+                    velocity,
+                    alt,
+                    accuracy,
+                    v_accuracy,
+                    cog,
+                    annotations,
+                    CASE WHEN {conditions} THEN 1 ELSE 0 END AS inside
+                FROM gpslog
+                WHERE (date(ts, 'unixepoch'), device_id) IN (SELECT date, device_id FROM tracks)
+                AND (accuracy IS NULL OR accuracy < {MAX_ACCURACY})
+            )
+            SELECT
+                device_id,
+                date,
+                seq,
+                lat as y,
+                lon as x,
+                datetime(ts, 'unixepoch') AS ts,
+                tid,
+                velocity as speed,
+                alt as elevation,
+                accuracy,
+                v_accuracy,
+                cog,
+                annotations
+            FROM (
+                SELECT
+                    fixes.*,
+                    ROW_NUMBER() OVER w AS seq,
+                    LAG(inside) OVER w AS prev_inside,
+                    LEAD(inside) OVER w AS next_inside
+                FROM fixes
+                WINDOW w AS (PARTITION BY device_id, date ORDER BY ts, id)
+            ) AS runs
+            WHERE inside = 1 OR prev_inside = 1 OR next_inside = 1
+            ORDER BY device_id, date, seq"#
+        );
+        let fixes: Vec<AreaTrackFix> = bind_area_filter(sqlx::query_as(&sql), filter, &cover)
+            .fetch_all(self.pool()?)
+            .await?;
+
+        let mut tracks: Vec<AreaTrack> = Vec::new();
+        let mut last_seq = 0;
+        for fix in fixes {
+            match tracks.last_mut() {
+                Some(track) if track.device_id == fix.device_id && track.date == fix.date => {
+                    // Gaps in the sequence separate runs
+                    if fix.seq == last_seq + 1 {
+                        if let Some(run) = track.runs.last_mut() {
+                            run.push(fix.point);
+                        }
+                    } else {
+                        track.runs.push(vec![fix.point]);
+                    }
+                }
+                _ => tracks.push(AreaTrack {
+                    device_id: fix.device_id,
+                    date: fix.date,
+                    runs: vec![vec![fix.point]],
+                }),
+            }
+            last_seq = fix.seq;
+        }
+        Ok(tracks)
+    }
+
+    /// Return visited regions with first and last visit
+    pub async fn query_region_visits(
+        &self,
+        device_id: Option<i32>,
+        date_from: Option<&str>,
+        date_to: Option<&str>,
+    ) -> anyhow::Result<Vec<RegionVisit>> {
+        let visits: Vec<RegionVisit> = sqlx::query_as(
+            r#"
+            SELECT
+                device_id,
+                region,
+                COUNT(*) AS days,
+                datetime(MIN(first_ts), 'unixepoch') AS first_visit,
+                datetime(MAX(last_ts), 'unixepoch') AS last_visit
+            FROM region_visits
+            WHERE ($1 IS NULL OR device_id = $1)
+            AND ($2 IS NULL OR date >= $2)
+            AND ($3 IS NULL OR date <= $3)
+            GROUP BY device_id, region
+            ORDER BY device_id, MIN(first_ts)
+            "#,
+        )
+        .bind(device_id)
+        .bind(date_from)
+        .bind(date_to)
+        .fetch_all(self.pool()?)
+        .await?;
+
+        Ok(visits)
+    }
+
+    /// Stream fixes of a device within a date range, ordered by time
+    pub fn stream_fixes<'a>(
+        &'a self,
+        device_id: i32,
+        date_from: Option<&'a str>,
+        date_to: Option<&'a str>,
+    ) -> BoxStream<'a, anyhow::Result<GpsPoint>> {
+        let pool = match self.pool() {
+            Ok(pool) => pool,
+            Err(e) => return stream::once(async { Err(e) }).boxed(),
+        };
+        // Timestamp bounds [ts_from, ts_to) of the date range
+        let bound = |date: Option<&str>, end: bool| match date {
+            None => Ok(None),
+            Some(date) => day_range(date)
+                .map(|(start, stop)| Some(if end { stop } else { start }))
+                .ok_or_else(|| anyhow::anyhow!("Invalid date '{date}'")),
+        };
+        let (ts_from, ts_to) = match (bound(date_from, false), bound(date_to, true)) {
+            (Ok(ts_from), Ok(ts_to)) => (ts_from, ts_to),
+            (Err(e), _) | (_, Err(e)) => return stream::once(async { Err(e) }).boxed(),
+        };
+        sqlx::query_as(
+            r#"
+                SELECT
+                    lat as y,
+                    lon as x,
+                    datetime(ts, 'unixepoch') AS ts,
+                    tid,
+                    velocity as speed,
+                    alt as elevation,
+                    accuracy,
+                    v_accuracy,
+                    cog,
+                    annotations
+                FROM gpslog
+                WHERE device_id = $1
+                AND ($2 IS NULL OR ts >= unixepoch($2, 'unixepoch'))
+                AND ($3 IS NULL OR ts < unixepoch($3, 'unixepoch'))
+                ORDER BY ts, id
+                "#,
+        )
+        .bind(device_id)
+        .bind(ts_from)
+        .bind(ts_to)
+        .fetch(pool)
+        .map_err(anyhow::Error::from)
+        .boxed()
+    }
+
     /// Return last device postitions
     pub async fn query_positions(&self, date: &str) -> anyhow::Result<Vec<Position>> {
+        #[cfg(feature = "duckdb")]
+        if let Backend::DuckDb(duckdb) = &self.backend {
+            return duckdb.query_positions(date).await;
+        }
         let positions: Vec<Position> = sqlx::query_as(
             r#"
             SELECT
@@ -257,7 +1174,7 @@ impl Db {
             "#,
         )
         .bind(date)
-        .fetch_all(&self.pool)
+        .fetch_all(self.pool()?)
         .await?;
 
         Ok(positions)
@@ -265,6 +1182,10 @@ impl Db {
 
     /// Check validity of invite
     pub async fn is_valid_invite(&self) -> anyhow::Result<bool> {
+        #[cfg(feature = "duckdb")]
+        if let Backend::DuckDb(duckdb) = &self.backend {
+            return duckdb.is_valid_invite().await;
+        }
         // Check URL token for friend invites (TODO)
         // For initial setup check existence of devices
         let initial_device = sqlx::query_scalar(
@@ -273,13 +1194,118 @@ impl Db {
                 FROM devices
                 "#,
         )
-        .fetch_one(&self.pool)
+        .fetch_one(self.pool()?)
         .await?;
 
         Ok(initial_device)
     }
 }
 
+/// SQL conditions of an area filter, using parameters bound by `bind_area_filter`
+///
+/// `cover` are the geohash ranges covering the bounding box (see `spatial_index::cover`).
+/// With PostGIS the bounding box is matched against the indexed geography instead.
+fn area_conditions(
+    filter: &AreaFilter,
+    cover: &[(String, Option<String>)],
+    postgis: bool,
+) -> String {
+    let bbox = if postgis {
+        "geog && ST_MakeEnvelope($1, $2, $3, $4, 4326)::geography"
+    } else {
+        "lat >= $2 AND lat <= $4 AND lon >= $1 AND lon <= $3"
+    };
+    let mut sql = format!("{bbox} AND (accuracy IS NULL OR accuracy < {MAX_ACCURACY})");
+    let mut param_no = 4;
+    // Restrict to the geohash cells covering the bounding box
+    if !cover.is_empty() {
+        let mut conditions = Vec::with_capacity(cover.len());
+        for (_, to) in cover {
+            param_no += 1;
+            let mut condition = format!("geohash >= ${param_no}");
+            if to.is_some() {
+                param_no += 1;
+                condition.push_str(&format!(" AND geohash < ${param_no}"));
+            }
+            conditions.push(format!("({condition})"));
+        }
+        sql.push_str(&format!(" AND ({})", conditions.join(" OR ")));
+    }
+    if filter.area.is_some() {
+        param_no += 1;
+        sql.push_str(&format!(
+            " AND ST_Intersects(geog, ST_SetSRID(ST_GeomFromGeoJSON(${param_no}), 4326)::geography)"
+        ));
+    }
+    if filter.date_from.is_some() {
+        param_no += 1;
+        sql.push_str(&format!(" AND date(ts, 'unixepoch') >= ${param_no}"));
+    }
+    if filter.date_to.is_some() {
+        param_no += 1;
+        sql.push_str(&format!(" AND date(ts, 'unixepoch') <= ${param_no}"));
+    }
+    if filter.device_id.is_some() {
+        param_no += 1;
+        sql.push_str(&format!(" AND device_id = ${param_no}"));
+    }
+    sql
+}
+
+/// Bind parameters of `area_conditions`
+fn bind_area_filter<'q, O>(
+    mut query: QueryAs<'q, Any, O, AnyArguments<'q>>,
+    filter: &'q AreaFilter,
+    cover: &'q [(String, Option<String>)],
+) -> QueryAs<'q, Any, O, AnyArguments<'q>> {
+    let [xmin, ymin, xmax, ymax] = filter.bbox;
+    query = query.bind(xmin).bind(ymin).bind(xmax).bind(ymax);
+    for (from, to) in cover {
+        query = query.bind(from.as_str());
+        if let Some(to) = to {
+            query = query.bind(to.as_str());
+        }
+    }
+    if let Some(area) = &filter.area {
+        query = query.bind(area);
+    }
+    if let Some(date_from) = &filter.date_from {
+        query = query.bind(date_from);
+    }
+    if let Some(date_to) = &filter.date_to {
+        query = query.bind(date_to);
+    }
+    if let Some(device_id) = filter.device_id {
+        query = query.bind(device_id);
+    }
+    query
+}
+
+/// Upsert first and last visit of a region on a day
+fn region_visit_query<'q>(
+    device_id: i64,
+    region: &'q str,
+    date: &'q str,
+    first: i64,
+    last: i64,
+) -> Query<'q, Any, AnyArguments<'q>> {
+    sqlx::query(
+        r#"
+        INSERT INTO region_visits (device_id, region, date, first_ts, last_ts)
+        VALUES ($1, $2, $3, unixepoch($4, 'unixepoch'), unixepoch($5, 'unixepoch'))
+        ON CONFLICT(device_id, region, date) DO UPDATE
+        SET first_ts = CASE WHEN excluded.first_ts < region_visits.first_ts
+                THEN excluded.first_ts ELSE region_visits.first_ts END,
+            last_ts = CASE WHEN excluded.last_ts > region_visits.last_ts
+                THEN excluded.last_ts ELSE region_visits.last_ts END"#,
+    )
+    .bind(device_id)
+    .bind(region)
+    .bind(date)
+    .bind(first)
+    .bind(last)
+}
+
 pub fn serialize_raw_json<S: Serializer>(v: &str, s: S) -> Result<S::Ok, S::Error> {
     let v: serde_json::Value =
         serde_json::from_str(v).map_err(|_| Error::custom("error parsing serialized json"))?;
@@ -295,3 +1321,141 @@ pub fn deserialize_dict_to_string<'de, D: Deserializer<'de>>(
         _ => Err(serde::de::Error::custom("expected a JSON object")),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::regions;
+    use sqlx::any::AnyPoolOptions;
+
+    // 2025-10-09 08:00:00 UTC
+    const TS: i64 = 1759996800;
+
+    async fn memory_db(boundaries: Option<Boundaries>) -> Db {
+        sqlx::any::install_default_drivers();
+        // A single connection keeps the in-memory database
+        let pool = AnyPoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        let db = Db {
+            backend: Backend::Sqlx(pool),
+            postgis: false,
+            boundaries: boundaries.map(Arc::new),
+            collapse_stationary: None,
+        };
+        db.run_migrations().await.unwrap();
+        db
+    }
+
+    /// Database sharing the connection with other boundaries
+    fn with_boundaries(db: &Db, boundaries: Option<Boundaries>) -> Db {
+        Db {
+            backend: Backend::Sqlx(db.pool().unwrap().clone()),
+            postgis: false,
+            boundaries: boundaries.map(Arc::new),
+            collapse_stationary: None,
+        }
+    }
+
+    fn location(x: f32, y: f32, ts: i64) -> Location {
+        Location {
+            tid: "me".to_string(),
+            ts,
+            velocity: None,
+            lat: y,
+            lon: x,
+            alt: None,
+            accuracy: Some(10),
+            v_accuracy: None,
+            cog: None,
+            annotations: "{}".to_string(),
+        }
+    }
+
+    async fn region_visits(db: &Db) -> Vec<(String, String, String)> {
+        sqlx::query_as(
+            r#"SELECT region, datetime(first_ts, 'unixepoch'), datetime(last_ts, 'unixepoch')
+            FROM region_visits ORDER BY region"#,
+        )
+        .fetch_all(db.pool().unwrap())
+        .await
+        .unwrap()
+    }
+
+    async fn last_id(db: &Db) -> (String, i64) {
+        sqlx::query_as("SELECT boundaries, last_id FROM region_visits_state")
+            .fetch_one(db.pool().unwrap())
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn incremental_region_visits() {
+        let db = memory_db(None).await;
+        // Fixes logged without boundaries
+        db.insert_location("user", "phone", &location(0.5, 0.5, TS))
+            .await
+            .unwrap();
+        db.insert_location("user", "phone", &location(1.5, 1.2, TS + 60))
+            .await
+            .unwrap();
+        let db = with_boundaries(&db, Some(regions::tests::boundaries("v1", "name")));
+        db.update_region_visits().await.unwrap();
+        assert_eq!(
+            region_visits(&db).await,
+            [
+                (
+                    "A".to_string(),
+                    "2025-10-09 08:00:00".to_string(),
+                    "2025-10-09 08:01:00".to_string()
+                ),
+                (
+                    "B".to_string(),
+                    "2025-10-09 08:01:00".to_string(),
+                    "2025-10-09 08:01:00".to_string()
+                ),
+            ]
+        );
+        assert_eq!(last_id(&db).await, ("v1".to_string(), 2));
+
+        // Only fixes logged since the last update are added, a recomputation would
+        // restore the first visit of the renamed region
+        sqlx::query("UPDATE region_visits SET region = 'old' WHERE region = 'A'")
+            .execute(db.pool().unwrap())
+            .await
+            .unwrap();
+        with_boundaries(&db, None)
+            .insert_location("user", "phone", &location(1.2, 1.5, TS + 120))
+            .await
+            .unwrap();
+        db.update_region_visits().await.unwrap();
+        let visits = region_visits(&db).await;
+        let regions: Vec<_> = visits
+            .iter()
+            .map(|(region, _, _)| region.as_str())
+            .collect();
+        assert_eq!(regions, ["A", "B", "old"]);
+        assert_eq!(visits[0].1, "2025-10-09 08:02:00");
+        assert_eq!(visits[1].2, "2025-10-09 08:02:00");
+        assert_eq!(last_id(&db).await.1, 3);
+    }
+
+    #[tokio::test]
+    async fn region_visits_source_change() {
+        let db = memory_db(Some(regions::tests::boundaries("v1", "name"))).await;
+        db.insert_location("user", "phone", &location(0.5, 0.5, TS))
+            .await
+            .unwrap();
+        db.update_region_visits().await.unwrap();
+        assert_eq!(region_visits(&db).await[0].0, "A");
+        // Other name field
+        let db = with_boundaries(&db, Some(regions::tests::boundaries("v1#id", "id")));
+        db.update_region_visits().await.unwrap();
+        let visits = region_visits(&db).await;
+        assert_eq!(visits.len(), 1);
+        assert_eq!(visits[0].0, "1");
+        assert_eq!(last_id(&db).await, ("v1#id".to_string(), 1));
+    }
+}
@@ -0,0 +1,478 @@
+use crate::db::GpsPoint;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tiff::decoder::{Decoder, DecodingResult};
+use tiff::tags::Tag;
+
+/// How DEM elevations are applied to track points
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DemMode {
+    /// Set elevation of points without altitude
+    Fill,
+    /// Replace all altitudes
+    Replace,
+}
+
+/// Elevation lookup from local SRTM HGT and GeoTIFF files (WGS84 only)
+pub struct Dem {
+    mode: DemMode,
+    dir: Option<PathBuf>,
+    /// Extents of GeoTIFF files in DEM directory
+    geotiffs: Vec<(PathBuf, Extent)>,
+    /// Recently used rasters
+    rasters: Mutex<RasterCache>,
+}
+
+/// Maximal number of rasters kept in memory between requests
+const MAX_CACHED_RASTERS: usize = 8;
+
+/// Loaded rasters by file, `None` for missing or invalid files
+type Rasters = HashMap<PathBuf, Option<Arc<Raster>>>;
+
+/// Least recently used rasters
+#[derive(Default)]
+struct RasterCache {
+    /// Raster and last use
+    rasters: HashMap<PathBuf, (Option<Arc<Raster>>, u64)>,
+    uses: u64,
+}
+
+impl RasterCache {
+    fn get(&mut self, path: &Path) -> Option<Option<Arc<Raster>>> {
+        self.uses += 1;
+        let (raster, last_use) = self.rasters.get_mut(path)?;
+        *last_use = self.uses;
+        Some(raster.clone())
+    }
+
+    /// Insert raster, evicting the least recently used ones
+    fn insert(&mut self, path: PathBuf, raster: Option<Arc<Raster>>) {
+        self.uses += 1;
+        self.rasters.insert(path, (raster, self.uses));
+        while self.rasters.len() > MAX_CACHED_RASTERS {
+            let Some(oldest) = self
+                .rasters
+                .iter()
+                .min_by_key(|(_, (_, last_use))| *last_use)
+                .map(|(path, _)| path.clone())
+            else {
+                break;
+            };
+            self.rasters.remove(&oldest);
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Extent {
+    xmin: f64,
+    ymin: f64,
+    xmax: f64,
+    ymax: f64,
+}
+
+impl Extent {
+    fn contains(&self, x: f64, y: f64) -> bool {
+        x >= self.xmin && x <= self.xmax && y >= self.ymin && y <= self.ymax
+    }
+}
+
+/// Elevation grid with cell values at cell centers
+struct Raster {
+    width: usize,
+    height: usize,
+    /// Coordinates of center of upper left cell
+    x0: f64,
+    y0: f64,
+    /// Cell size in degrees
+    dx: f64,
+    dy: f64,
+    nodata: Option<f32>,
+    data: Vec<f32>,
+}
+
+impl Dem {
+    pub fn from_env() -> Self {
+        let dir = dotenvy::var("DEM_DIR").ok().filter(|dir| !dir.is_empty());
+        let mode = match dotenvy::var("DEM_MODE").as_deref() {
+            Ok("replace") => DemMode::Replace,
+            _ => DemMode::Fill,
+        };
+        Self::new(dir.map(PathBuf::from), mode)
+    }
+
+    pub fn new(dir: Option<PathBuf>, mode: DemMode) -> Self {
+        let geotiffs = dir
+            .as_ref()
+            .map(|dir| scan_geotiffs(dir))
+            .unwrap_or_default();
+        if let Some(dir) = &dir {
+            log::info!(
+                "Using elevation model files in `{}` ({} GeoTIFF files)",
+                dir.display(),
+                geotiffs.len()
+            );
+        }
+        Dem {
+            mode,
+            dir,
+            geotiffs,
+            rasters: Mutex::new(RasterCache::default()),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.dir.is_some()
+    }
+
+    /// Fill or replace elevations of track points
+    pub async fn correct(&self, points: &mut [GpsPoint]) {
+        if !self.is_enabled() {
+            return;
+        }
+        let points: Vec<&mut GpsPoint> = points
+            .iter_mut()
+            .filter(|pt| self.mode == DemMode::Replace || pt.elevation.is_none())
+            .collect();
+        let mut paths: Vec<PathBuf> = points
+            .iter()
+            .flat_map(|pt| self.candidates(pt.x, pt.y))
+            .collect();
+        paths.sort();
+        paths.dedup();
+        let rasters = self.load_rasters(paths).await;
+        for pt in points {
+            if let Some(elevation) = self.elevation(&rasters, pt.x, pt.y) {
+                pt.elevation = Some(elevation.round() as i16);
+                pt.dem_elevation = true;
+            }
+        }
+    }
+
+    /// Files possibly containing a WGS84 position, HGT file first
+    fn candidates(&self, x: f64, y: f64) -> Vec<PathBuf> {
+        let Some(dir) = &self.dir else {
+            return Vec::new();
+        };
+        std::iter::once(dir.join(hgt_file_name(x, y)))
+            .chain(
+                self.geotiffs
+                    .iter()
+                    .filter(|(_, extent)| extent.contains(x, y))
+                    .map(|(path, _)| path.clone()),
+            )
+            .collect()
+    }
+
+    /// Elevation at a WGS84 position from loaded rasters
+    fn elevation(&self, rasters: &Rasters, x: f64, y: f64) -> Option<f64> {
+        self.candidates(x, y)
+            .iter()
+            .filter_map(|path| rasters.get(path).cloned().flatten())
+            .find_map(|raster| raster.interpolate(x, y))
+    }
+
+    /// Get rasters from the cache or read them in a blocking task
+    async fn load_rasters(&self, paths: Vec<PathBuf>) -> Rasters {
+        let mut rasters = Rasters::new();
+        let mut missing = Vec::new();
+        {
+            let mut cache = self.rasters.lock().unwrap();
+            for path in paths {
+                match cache.get(&path) {
+                    Some(raster) => {
+                        rasters.insert(path, raster);
+                    }
+                    None => missing.push(path),
+                }
+            }
+        }
+        if missing.is_empty() {
+            return rasters;
+        }
+        let loaded = tokio::task::spawn_blocking(move || {
+            missing
+                .into_iter()
+                .map(|path| {
+                    let raster = read_raster(&path);
+                    (path, raster)
+                })
+                .collect::<Vec<_>>()
+        })
+        .await;
+        match loaded {
+            Ok(loaded) => {
+                let mut cache = self.rasters.lock().unwrap();
+                for (path, raster) in loaded {
+                    cache.insert(path.clone(), raster.clone());
+                    rasters.insert(path, raster);
+                }
+            }
+            Err(e) => log::error!("Failed to read elevation models: {e}"),
+        }
+        rasters
+    }
+}
+
+/// Read HGT or GeoTIFF file, `None` for missing or invalid files
+fn read_raster(path: &Path) -> Option<Arc<Raster>> {
+    if !path.exists() {
+        return None;
+    }
+    let raster = if path.extension().is_some_and(|ext| ext == "hgt") {
+        read_hgt(path)
+    } else {
+        read_geotiff(path)
+    };
+    match raster {
+        Ok(raster) => Some(Arc::new(raster)),
+        Err(e) => {
+            log::error!("Failed to read elevation model `{}`: {e}", path.display());
+            None
+        }
+    }
+}
+
+impl Raster {
+    fn value(&self, col: usize, row: usize) -> Option<f64> {
+        let value = *self.data.get(row * self.width + col)?;
+        if value.is_nan() || Some(value) == self.nodata || value <= -32768.0 {
+            None
+        } else {
+            Some(value as f64)
+        }
+    }
+
+    /// Bilinear interpolation, ignoring missing values
+    fn interpolate(&self, x: f64, y: f64) -> Option<f64> {
+        let fc = (x - self.x0) / self.dx;
+        let fr = (self.y0 - y) / self.dy;
+        if fc < -0.5 || fr < -0.5 || fc > self.width as f64 - 0.5 || fr > self.height as f64 - 0.5 {
+            return None;
+        }
+        let fc = fc.clamp(0.0, (self.width - 1) as f64);
+        let fr = fr.clamp(0.0, (self.height - 1) as f64);
+        let (c0, r0) = (fc.floor() as usize, fr.floor() as usize);
+        let (c1, r1) = ((c0 + 1).min(self.width - 1), (r0 + 1).min(self.height - 1));
+        let (tx, ty) = (fc - c0 as f64, fr - r0 as f64);
+        let samples = [
+            (self.value(c0, r0), (1.0 - tx) * (1.0 - ty)),
+            (self.value(c1, r0), tx * (1.0 - ty)),
+            (self.value(c0, r1), (1.0 - tx) * ty),
+            (self.value(c1, r1), tx * ty),
+        ];
+        let (sum, weights) = samples
+            .iter()
+            .filter_map(|(value, w)| value.map(|v| (v * w, *w)))
+            .fold((0.0, 0.0), |(sum, weights), (v, w)| (sum + v, weights + w));
+        (weights > 0.0).then(|| sum / weights)
+    }
+}
+
+/// SRTM file name of the tile containing a position, e.g. `N47E009.hgt`
+fn hgt_file_name(x: f64, y: f64) -> String {
+    let (lon, lat) = (x.floor() as i32, y.floor() as i32);
+    format!(
+        "{}{:02}{}{:03}.hgt",
+        if lat >= 0 { 'N' } else { 'S' },
+        lat.abs(),
+        if lon >= 0 { 'E' } else { 'W' },
+        lon.abs()
+    )
+}
+
+/// Read SRTM HGT file (big-endian 16 bit integers, 1 or 3 arc-second grid)
+fn read_hgt(path: &Path) -> anyhow::Result<Raster> {
+    let name = path
+        .file_stem()
+        .and_then(|name| name.to_str())
+        .unwrap_or_default()
+        .to_uppercase();
+    if name.len() != 7 {
+        anyhow::bail!("unexpected file name");
+    }
+    let lat: f64 = name[1..3].parse()?;
+    let lon: f64 = name[4..7].parse()?;
+    let lat = if name.starts_with('S') { -lat } else { lat };
+    let lon = if name[3..].starts_with('W') {
+        -lon
+    } else {
+        lon
+    };
+    let bytes = std::fs::read(path)?;
+    let size = ((bytes.len() / 2) as f64).sqrt() as usize;
+    if size < 2 || size * size * 2 != bytes.len() {
+        anyhow::bail!("unexpected file size {}", bytes.len());
+    }
+    let data = bytes
+        .chunks_exact(2)
+        .map(|b| i16::from_be_bytes([b[0], b[1]]) as f32)
+        .collect();
+    let cell_size = 1.0 / (size - 1) as f64;
+    Ok(Raster {
+        width: size,
+        height: size,
+        x0: lon,
+        y0: lat + 1.0,
+        dx: cell_size,
+        dy: cell_size,
+        nodata: Some(-32768.0),
+        data,
+    })
+}
+
+/// GeoTIFF georeferencing: dimensions, upper left corner and cell size
+fn geotiff_georef<R: std::io::Read + std::io::Seek>(
+    decoder: &mut Decoder<R>,
+) -> anyhow::Result<(usize, usize, f64, f64, f64, f64)> {
+    let (width, height) = decoder.dimensions()?;
+    let scale = decoder.get_tag_f64_vec(Tag::ModelPixelScaleTag)?;
+    let tiepoint = decoder.get_tag_f64_vec(Tag::ModelTiepointTag)?;
+    if scale.len() < 2 || tiepoint.len() < 6 {
+        anyhow::bail!("invalid georeferencing tags");
+    }
+    // Tiepoint: raster (i, j, k) -> model (x, y, z)
+    let (dx, dy) = (scale[0], scale[1]);
+    let xmin = tiepoint[3] - tiepoint[0] * dx;
+    let ymax = tiepoint[4] + tiepoint[1] * dy;
+    Ok((width as usize, height as usize, xmin, ymax, dx, dy))
+}
+
+fn scan_geotiffs(dir: &Path) -> Vec<(PathBuf, Extent)> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            log::error!("Failed to read DEM directory `{}`: {e}", dir.display());
+            return Vec::new();
+        }
+    };
+    entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.extension()
+                .and_then(|ext| ext.to_str())
+                .is_some_and(|ext| ["tif", "tiff"].contains(&ext.to_lowercase().as_str()))
+        })
+        .filter_map(|path| {
+            let georef = File::open(&path)
+                .map_err(anyhow::Error::from)
+                .and_then(|file| Ok(Decoder::new(BufReader::new(file))?))
+                .and_then(|mut decoder| geotiff_georef(&mut decoder));
+            match georef {
+                Ok((width, height, xmin, ymax, dx, dy)) => {
+                    let extent = Extent {
+                        xmin,
+                        ymin: ymax - height as f64 * dy,
+                        xmax: xmin + width as f64 * dx,
+                        ymax,
+                    };
+                    Some((path, extent))
+                }
+                Err(e) => {
+                    log::warn!("Ignoring GeoTIFF `{}`: {e}", path.display());
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+/// Read single band GeoTIFF in geographic coordinates
+fn read_geotiff(path: &Path) -> anyhow::Result<Raster> {
+    let mut decoder = Decoder::new(BufReader::new(File::open(path)?))?;
+    let (width, height, xmin, ymax, dx, dy) = geotiff_georef(&mut decoder)?;
+    let nodata = decoder
+        .get_tag_ascii_string(Tag::GdalNodata)
+        .ok()
+        .and_then(|s| s.trim_matches(char::from(0)).trim().parse().ok());
+    let data: Vec<f32> = match decoder.read_image()? {
+        DecodingResult::U8(v) => v.into_iter().map(f32::from).collect(),
+        DecodingResult::U16(v) => v.into_iter().map(f32::from).collect(),
+        DecodingResult::I8(v) => v.into_iter().map(f32::from).collect(),
+        DecodingResult::I16(v) => v.into_iter().map(f32::from).collect(),
+        DecodingResult::U32(v) => v.into_iter().map(|v| v as f32).collect(),
+        DecodingResult::I32(v) => v.into_iter().map(|v| v as f32).collect(),
+        DecodingResult::U64(v) => v.into_iter().map(|v| v as f32).collect(),
+        DecodingResult::I64(v) => v.into_iter().map(|v| v as f32).collect(),
+        DecodingResult::F32(v) => v,
+        DecodingResult::F64(v) => v.into_iter().map(|v| v as f32).collect(),
+    };
+    if data.len() != width * height {
+        anyhow::bail!("only single band rasters are supported");
+    }
+    Ok(Raster {
+        width,
+        height,
+        x0: xmin + dx / 2.0,
+        y0: ymax - dy / 2.0,
+        dx,
+        dy,
+        nodata,
+        data,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hgt_names() {
+        assert_eq!(hgt_file_name(9.43, 47.05), "N47E009.hgt");
+        assert_eq!(hgt_file_name(-0.5, -33.9), "S34W001.hgt");
+    }
+
+    #[tokio::test]
+    async fn correct_from_hgt() {
+        let dir = std::env::temp_dir().join(format!("owntrack-dem-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        // 2x2 grid from north-west to south-east
+        let data: Vec<u8> = [100i16, 200, 300, 400]
+            .iter()
+            .flat_map(|v| v.to_be_bytes())
+            .collect();
+        std::fs::write(dir.join("N47E009.hgt"), data).unwrap();
+        let dem = Dem::new(Some(dir.clone()), DemMode::Fill);
+        let mut points = vec![
+            GpsPoint {
+                x: 9.5,
+                y: 47.5,
+                ..Default::default()
+            },
+            GpsPoint {
+                x: 9.0,
+                y: 48.0,
+                elevation: Some(1),
+                ..Default::default()
+            },
+            GpsPoint {
+                x: 10.5,
+                y: 47.5,
+                ..Default::default()
+            },
+        ];
+        dem.correct(&mut points).await;
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(points[0].elevation, Some(250));
+        assert!(points[0].dem_elevation);
+        assert_eq!(points[1].elevation, Some(1));
+        assert_eq!(points[2].elevation, None);
+    }
+
+    #[test]
+    fn evict_least_recently_used() {
+        let mut cache = RasterCache::default();
+        for i in 0..MAX_CACHED_RASTERS {
+            cache.insert(PathBuf::from(format!("{i}.hgt")), None);
+        }
+        assert!(cache.get(Path::new("0.hgt")).is_some());
+        cache.insert(PathBuf::from("new.hgt"), None);
+        assert_eq!(cache.rasters.len(), MAX_CACHED_RASTERS);
+        assert!(cache.get(Path::new("0.hgt")).is_some());
+        assert!(cache.get(Path::new("1.hgt")).is_none());
+        assert!(cache.get(Path::new("new.hgt")).is_some());
+    }
+}
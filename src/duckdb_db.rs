@@ -0,0 +1,333 @@
+//! Embedded DuckDB storage backend.
+//!
+//! Stores locations in the same table layout as the SQL backends, so the database file
+//! can be analysed directly with DuckDB and its spatial extension.
+
+use crate::db::{GpsPoint, Position, TrackData, TrackInfo, TrackRef};
+use crate::owntracks::Location;
+use crate::spatial_index;
+use duckdb::{params, Connection, Row};
+use std::sync::{Arc, Mutex};
+
+const SCHEMA: &str = r#"
+    CREATE SEQUENCE IF NOT EXISTS devices_id_seq;
+    CREATE TABLE IF NOT EXISTS devices(
+        id INTEGER PRIMARY KEY DEFAULT nextval('devices_id_seq'),
+        user_id VARCHAR NOT NULL,
+        device VARCHAR NOT NULL,
+        -- last position information
+        lat DOUBLE NOT NULL,
+        lon DOUBLE NOT NULL,
+        ts TIMESTAMP NOT NULL, -- UTC
+        tid VARCHAR NOT NULL,
+        velocity SMALLINT,
+        alt SMALLINT,
+        accuracy INTEGER,
+        v_accuracy SMALLINT,
+        cog SMALLINT,
+        UNIQUE (user_id, device)
+    );
+    CREATE SEQUENCE IF NOT EXISTS gpslog_id_seq;
+    CREATE TABLE IF NOT EXISTS gpslog(
+        id BIGINT PRIMARY KEY DEFAULT nextval('gpslog_id_seq'),
+        device_id INTEGER NOT NULL,
+        lat DOUBLE NOT NULL,
+        lon DOUBLE NOT NULL,
+        ts TIMESTAMP NOT NULL, -- UTC
+        tid VARCHAR NOT NULL,
+        velocity SMALLINT,
+        alt SMALLINT,
+        accuracy INTEGER,
+        v_accuracy SMALLINT,
+        cog SMALLINT,
+        annotations VARCHAR DEFAULT '{}' NOT NULL,
+        geohash VARCHAR
+    );
+"#;
+
+/// Timestamp format of query results, compatible with SQLite `datetime`
+const TS_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+#[derive(Clone)]
+pub struct DuckDb {
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl DuckDb {
+    pub fn open(path: &str) -> anyhow::Result<Self> {
+        log::info!("Opening DuckDB database {path}");
+        let conn = Connection::open(path)?;
+        Ok(DuckDb {
+            conn: Arc::new(Mutex::new(conn)),
+        })
+    }
+
+    /// Run a blocking database operation
+    async fn run<T, F>(&self, f: F) -> anyhow::Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Connection) -> duckdb::Result<T> + Send + 'static,
+    {
+        let conn = self.conn.clone();
+        tokio::task::spawn_blocking(move || {
+            let conn = conn
+                .lock()
+                .map_err(|_| anyhow::anyhow!("DuckDB connection poisoned"))?;
+            Ok(f(&conn)?)
+        })
+        .await?
+    }
+
+    pub async fn run_migrations(&self) -> anyhow::Result<()> {
+        self.run(|conn| conn.execute_batch(SCHEMA)).await
+    }
+
+    pub async fn insert_location(
+        &self,
+        user: &str,
+        device: &str,
+        loc: &Location,
+    ) -> anyhow::Result<()> {
+        let (user, device, loc) = (user.to_string(), device.to_string(), loc.clone());
+        self.run(move |conn| {
+            let tx = conn.unchecked_transaction()?;
+            // Upsert device location
+            let device_id: i32 = tx.query_row(
+                r#"
+                INSERT INTO devices (user_id, device, tid, ts, velocity, lat, lon, alt, accuracy, v_accuracy, cog)
+                VALUES (?, ?, ?, make_timestamp(?::BIGINT * 1000000), ?, ?, ?, ?, ?, ?, ?)
+                ON CONFLICT (user_id, device) DO UPDATE
+                SET tid = excluded.tid, ts = excluded.ts, velocity = excluded.velocity,
+                    lat = excluded.lat, lon = excluded.lon, alt = excluded.alt,
+                    accuracy = excluded.accuracy, v_accuracy = excluded.v_accuracy, cog = excluded.cog
+                RETURNING id"#,
+                params![
+                    user,
+                    device,
+                    loc.tid,
+                    loc.ts,
+                    loc.velocity,
+                    loc.lat,
+                    loc.lon,
+                    loc.alt,
+                    loc.accuracy,
+                    loc.v_accuracy,
+                    loc.cog,
+                ],
+                |row| row.get(0),
+            )?;
+            tx.execute(
+                r#"
+                INSERT INTO gpslog
+                (device_id, tid, ts, velocity, lat, lon, alt, accuracy, v_accuracy, cog, annotations, geohash)
+                VALUES (?, ?, make_timestamp(?::BIGINT * 1000000), ?, ?, ?, ?, ?, ?, ?, ?, ?)"#,
+                params![
+                    device_id,
+                    loc.tid,
+                    loc.ts,
+                    loc.velocity,
+                    loc.lat,
+                    loc.lon,
+                    loc.alt,
+                    loc.accuracy,
+                    loc.v_accuracy,
+                    loc.cog,
+                    loc.annotations,
+                    spatial_index::encode(loc.lon as f64, loc.lat as f64).unwrap_or_default(),
+                ],
+            )?;
+            tx.commit()
+        })
+        .await
+    }
+
+    /// Return track infos of a given date
+    pub async fn query_tracks_info(&self, date: &str) -> anyhow::Result<Vec<TrackInfo>> {
+        let date = date.to_string();
+        self.run(move |conn| {
+            let mut stmt = conn.prepare(
+                r#"
+                SELECT
+                    device_id,
+                    user_id,
+                    device,
+                    devices.tid,
+                    strftime(min(gpslog.ts), ?) AS ts_start,
+                    strftime(max(gpslog.ts), ?) AS ts_end
+                FROM gpslog
+                JOIN devices ON gpslog.device_id = devices.id
+                WHERE gpslog.ts::DATE = ?::DATE
+                GROUP BY device_id, user_id, device, devices.tid
+                ORDER BY ts_end DESC"#,
+            )?;
+            let tracks = stmt.query_map(params![TS_FORMAT, TS_FORMAT, date], |row| {
+                Ok(TrackInfo {
+                    device_id: row.get(0)?,
+                    user_id: row.get(1)?,
+                    device: row.get(2)?,
+                    tid: row.get(3)?,
+                    ts_start: row.get(4)?,
+                    ts_end: row.get(5)?,
+                    distance: None,
+                    bbox: None,
+                })
+            })?;
+            tracks.collect()
+        })
+        .await
+    }
+
+    pub async fn query_track(&self, track_ref: &TrackRef) -> anyhow::Result<TrackData> {
+        let date = track_ref.date();
+        let device_id = track_ref.device_id;
+        let query_date = date.clone();
+        let points = self
+            .run(move |conn| {
+                let mut stmt = conn.prepare(
+                    r#"
+                    SELECT lat, lon, strftime(ts, ?), tid, velocity, alt, accuracy, v_accuracy, cog, annotations
+                    FROM gpslog
+                    WHERE ts::DATE = ?::DATE
+                    AND device_id = ?
+                    ORDER BY id"#,
+                )?;
+                let points = stmt.query_map(params![TS_FORMAT, query_date, device_id], gps_point)?;
+                points.collect()
+            })
+            .await?;
+
+        Ok(TrackData {
+            device_id,
+            date,
+            points,
+        })
+    }
+
+    /// Return last device postitions
+    pub async fn query_positions(&self, date: &str) -> anyhow::Result<Vec<Position>> {
+        let date = date.to_string();
+        self.run(move |conn| {
+            let mut stmt = conn.prepare(
+                r#"
+                SELECT id, lat, lon, strftime(ts, ?), tid, velocity, alt, accuracy, v_accuracy, cog
+                FROM devices
+                WHERE ts::DATE = ?::DATE"#,
+            )?;
+            let positions = stmt.query_map(params![TS_FORMAT, date], |row| {
+                Ok(Position {
+                    device_id: row.get(0)?,
+                    y: row.get(1)?,
+                    x: row.get(2)?,
+                    ts: row.get(3)?,
+                    tid: row.get(4)?,
+                    speed: row.get(5)?,
+                    elevation: row.get(6)?,
+                    accuracy: row.get(7)?,
+                    v_accuracy: row.get(8)?,
+                    cog: row.get(9)?,
+                })
+            })?;
+            positions.collect()
+        })
+        .await
+    }
+
+    pub async fn is_valid_invite(&self) -> anyhow::Result<bool> {
+        self.run(|conn| conn.query_row("SELECT COUNT(*) = 0 FROM devices", [], |row| row.get(0)))
+            .await
+    }
+}
+
+fn gps_point(row: &Row) -> duckdb::Result<GpsPoint> {
+    Ok(GpsPoint {
+        y: row.get(0)?,
+        x: row.get(1)?,
+        ts: row.get(2)?,
+        tid: row.get(3)?,
+        speed: row.get(4)?,
+        elevation: row.get(5)?,
+        accuracy: row.get(6)?,
+        v_accuracy: row.get(7)?,
+        cog: row.get(8)?,
+        annotations: row.get(9)?,
+        dem_elevation: false,
+    })
+}
+
+#[cfg(all(test, feature = "duckdb"))]
+mod tests {
+    use super::*;
+
+    /// 2025-10-09 08:00:00 UTC
+    const TS: i64 = 1759996800;
+
+    fn location(ts: i64, lat: f32) -> Location {
+        Location {
+            tid: "me".to_string(),
+            ts,
+            velocity: Some(5),
+            lat,
+            lon: 9.43,
+            alt: Some(450),
+            accuracy: Some(10),
+            v_accuracy: Some(3),
+            cog: Some(90),
+            annotations: r#"{"batt":80}"#.to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn round_trip() {
+        let db = DuckDb {
+            conn: Arc::new(Mutex::new(Connection::open_in_memory().unwrap())),
+        };
+        db.run_migrations().await.unwrap();
+        assert!(db.is_valid_invite().await.unwrap());
+        db.insert_location("user", "phone", &location(TS, 47.05))
+            .await
+            .unwrap();
+        db.insert_location("user", "phone", &location(TS + 60, 47.06))
+            .await
+            .unwrap();
+        assert!(!db.is_valid_invite().await.unwrap());
+
+        let tracks = db.query_tracks_info("2025-10-09").await.unwrap();
+        assert_eq!(tracks.len(), 1);
+        let track = &tracks[0];
+        assert_eq!(
+            (track.user_id.as_str(), track.device.as_str()),
+            ("user", "phone")
+        );
+        assert_eq!(track.ts_start, "2025-10-09 08:00:00");
+        assert_eq!(track.ts_end, "2025-10-09 08:01:00");
+        assert!(db.query_tracks_info("2025-10-10").await.unwrap().is_empty());
+
+        let track_ref = TrackRef {
+            device_id: track.device_id,
+            ts_start: track.ts_start.clone(),
+            segmented: None,
+            collapse: None,
+            smooth: None,
+            simplify: None,
+            tolerance: None,
+            zoom: None,
+        };
+        let data = db.query_track(&track_ref).await.unwrap();
+        assert_eq!(data.date, "2025-10-09");
+        assert_eq!(data.points.len(), 2);
+        let pt = &data.points[1];
+        assert_eq!(pt.ts, "2025-10-09 08:01:00");
+        assert!((pt.y - 47.06).abs() < 1e-5);
+        assert_eq!(
+            (pt.speed, pt.elevation, pt.accuracy, pt.cog),
+            (Some(5), Some(450), Some(10), Some(90))
+        );
+        assert_eq!(pt.annotations, r#"{"batt":80}"#);
+
+        let positions = db.query_positions("2025-10-09").await.unwrap();
+        assert_eq!(positions.len(), 1);
+        assert_eq!(positions[0].device_id, track.device_id);
+        assert_eq!(positions[0].ts, "2025-10-09 08:01:00");
+        assert!((positions[0].y - 47.06).abs() < 1e-5);
+    }
+}
@@ -0,0 +1,217 @@
+use crate::db::GpsPoint;
+use crate::interpolate;
+use geo::{Distance, Haversine, Point};
+use serde::Deserialize;
+
+#[derive(Deserialize, Debug)]
+pub struct EncounterParams {
+    pub date: String,
+    /// Maximal distance between devices in meters (max. 10000)
+    pub distance: Option<f64>,
+    /// Minimal duration in seconds (max. 86400)
+    pub min_duration: Option<i64>,
+    /// Time between compared positions in seconds
+    pub step: Option<i64>,
+    /// Maximal time to the nearest fix in seconds (max. 86400)
+    pub max_gap: Option<i64>,
+}
+
+/// Upper limit of the maximal distance between devices (meters)
+const MAX_DISTANCE_LIMIT: f64 = 10_000.0;
+/// Upper limit of the minimal duration of an encounter (seconds)
+const MIN_DURATION_LIMIT: i64 = 24 * 3600;
+
+impl EncounterParams {
+    pub fn distance(&self) -> f64 {
+        self.distance
+            .unwrap_or(100.0)
+            .clamp(0.0, MAX_DISTANCE_LIMIT)
+    }
+    pub fn min_duration(&self) -> i64 {
+        self.min_duration
+            .unwrap_or(600)
+            .clamp(0, MIN_DURATION_LIMIT)
+    }
+    pub fn step(&self) -> i64 {
+        self.step.unwrap_or(60).max(10)
+    }
+    pub fn max_gap(&self) -> i64 {
+        interpolate::max_gap(self.max_gap, 600)
+    }
+}
+
+/// Period where two devices were close to each other
+#[derive(Debug)]
+pub struct Encounter {
+    pub device_ids: [i32; 2],
+    /// Seconds since epoch
+    pub ts_start: i64,
+    pub ts_end: i64,
+    /// Center of positions of both devices
+    pub x: f64,
+    pub y: f64,
+    /// Minimal distance between devices (meters)
+    pub min_distance: f64,
+}
+
+/// Open encounter while comparing positions
+struct Run {
+    ts_start: i64,
+    ts_end: i64,
+    sum_x: f64,
+    sum_y: f64,
+    count: usize,
+    min_distance: f64,
+}
+
+/// Detect encounters between all pairs of devices using time aligned positions
+pub fn encounters(devices: &[(i32, Vec<GpsPoint>)], params: &EncounterParams) -> Vec<Encounter> {
+    let device_fixes: Vec<_> = devices
+        .iter()
+        .map(|(device_id, points)| (*device_id, interpolate::timed_fixes(points)))
+        .filter(|(_, fixes)| !fixes.is_empty())
+        .collect();
+    let mut encounters = Vec::new();
+    for (i, (id_a, fixes_a)) in device_fixes.iter().enumerate() {
+        for (id_b, fixes_b) in &device_fixes[i + 1..] {
+            // Common time range
+            let ts_from = fixes_a[0].0.max(fixes_b[0].0);
+            let ts_to = fixes_a[fixes_a.len() - 1]
+                .0
+                .min(fixes_b[fixes_b.len() - 1].0);
+            let mut run: Option<Run> = None;
+            let mut ts = ts_from;
+            while ts <= ts_to + params.step() {
+                let close = if ts <= ts_to {
+                    interpolate::position_at(fixes_a, ts, params.max_gap())
+                        .zip(interpolate::position_at(fixes_b, ts, params.max_gap()))
+                        .map(|(a, b)| {
+                            let distance =
+                                Haversine::distance(Point::new(a.x, a.y), Point::new(b.x, b.y));
+                            ((a.x + b.x) / 2.0, (a.y + b.y) / 2.0, distance)
+                        })
+                        .filter(|(_, _, distance)| *distance <= params.distance())
+                } else {
+                    None
+                };
+                match (close, run.as_mut()) {
+                    (Some((x, y, distance)), Some(run)) => {
+                        run.ts_end = ts;
+                        run.sum_x += x;
+                        run.sum_y += y;
+                        run.count += 1;
+                        run.min_distance = run.min_distance.min(distance);
+                    }
+                    (Some((x, y, distance)), None) => {
+                        run = Some(Run {
+                            ts_start: ts,
+                            ts_end: ts,
+                            sum_x: x,
+                            sum_y: y,
+                            count: 1,
+                            min_distance: distance,
+                        });
+                    }
+                    (None, _) => {
+                        if let Some(run) = run.take() {
+                            if run.ts_end - run.ts_start >= params.min_duration() {
+                                encounters.push(Encounter {
+                                    device_ids: [*id_a, *id_b],
+                                    ts_start: run.ts_start,
+                                    ts_end: run.ts_end,
+                                    x: run.sum_x / run.count as f64,
+                                    y: run.sum_y / run.count as f64,
+                                    min_distance: run.min_distance,
+                                });
+                            }
+                        }
+                    }
+                }
+                ts += params.step();
+            }
+        }
+    }
+    encounters.sort_by_key(|encounter| encounter.ts_start);
+    encounters
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::DateTime;
+
+    // 2025-10-09 08:00:00 UTC
+    const TS: i64 = 1759996800;
+
+    /// Fixes at the equator, `x` by minutes from 08:00
+    fn track(fixes: &[(i64, f64)]) -> Vec<GpsPoint> {
+        fixes
+            .iter()
+            .map(|(minutes, x)| GpsPoint {
+                x: *x,
+                y: 0.0,
+                ts: DateTime::from_timestamp(TS + minutes * 60, 0)
+                    .unwrap()
+                    .format("%F %T")
+                    .to_string(),
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    fn params() -> EncounterParams {
+        EncounterParams {
+            date: "2025-10-09".to_string(),
+            distance: None,
+            min_duration: None,
+            step: None,
+            max_gap: None,
+        }
+    }
+
+    #[test]
+    fn tracks_meeting() {
+        let a = track(&(0..=60).map(|m| (m, 0.0)).collect::<Vec<_>>());
+        // About 1 km away, 33 m away from 08:15 to 08:45
+        let mut fixes = vec![(0, 0.01), (10, 0.01)];
+        fixes.extend((15..=45).map(|m| (m, 0.0003)));
+        fixes.extend([(50, 0.01), (60, 0.01)]);
+        let b = track(&fixes);
+        let encounters = encounters(&[(1, a), (2, b)], &params());
+        assert_eq!(encounters.len(), 1);
+        let encounter = &encounters[0];
+        assert_eq!(encounter.device_ids, [1, 2]);
+        assert_eq!(encounter.ts_start, TS + 15 * 60);
+        assert_eq!(encounter.ts_end, TS + 45 * 60);
+        assert!((encounter.x - 0.00015).abs() < 1e-9);
+        assert!((encounter.min_distance - 33.4).abs() < 0.1);
+    }
+
+    #[test]
+    fn encounter_ends_at_gap() {
+        let a = track(&(0..=90).map(|m| (m, 0.0)).collect::<Vec<_>>());
+        // No fixes between 08:30 and 09:30
+        let mut fixes: Vec<_> = (15..=30).map(|m| (m, 0.0003)).collect();
+        fixes.push((90, 0.0003));
+        let b = track(&fixes);
+        let encounters = encounters(&[(1, a), (2, b)], &params());
+        let ranges: Vec<_> = encounters
+            .iter()
+            .map(|encounter| (encounter.ts_start - TS, encounter.ts_end - TS))
+            .collect();
+        assert_eq!(ranges, [(15 * 60, 40 * 60), (80 * 60, 90 * 60)]);
+    }
+
+    #[test]
+    fn clamp_params() {
+        let params = EncounterParams {
+            distance: Some(1e9),
+            min_duration: Some(-1),
+            max_gap: Some(i64::MAX),
+            ..params()
+        };
+        assert_eq!(params.distance(), MAX_DISTANCE_LIMIT);
+        assert_eq!(params.min_duration(), 0);
+        assert_eq!(params.max_gap(), interpolate::MAX_GAP_LIMIT);
+    }
+}
@@ -0,0 +1,265 @@
+//! Explorer tiles: map tiles of a zoom level visited by the tracks of a device.
+
+use crate::db::{parse_timestamp, Db};
+use crate::geojson::MAX_ACCURACY;
+use crate::mvt::{self, TileCoord};
+use futures_util::StreamExt;
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet, VecDeque};
+
+const DEFAULT_ZOOM: u8 = 14;
+const MIN_ZOOM: u8 = 10;
+const MAX_ZOOM: u8 = 17;
+
+#[derive(Deserialize, Debug)]
+pub struct ExplorerParams {
+    pub device_id: i32,
+    /// Zoom level of tiles (10-17)
+    pub zoom: Option<u8>,
+    /// Maximal time between connected fixes in seconds
+    pub max_gap: Option<i64>,
+}
+
+impl ExplorerParams {
+    pub fn zoom(&self) -> u8 {
+        self.zoom.unwrap_or(DEFAULT_ZOOM).clamp(MIN_ZOOM, MAX_ZOOM)
+    }
+    pub fn max_gap(&self) -> i64 {
+        self.max_gap.unwrap_or(600)
+    }
+}
+
+/// Visited tiles with derived statistics
+#[derive(Debug)]
+pub struct ExplorerTiles {
+    pub zoom: u8,
+    /// Tiles with date of first visit in format 2025-02-19
+    pub tiles: HashMap<(u32, u32), String>,
+    /// Largest connected set of tiles with all four neighbours visited
+    pub max_cluster: HashSet<(u32, u32)>,
+    /// Upper left tile and size of the largest visited square
+    pub max_square: Option<((u32, u32), u32)>,
+}
+
+impl ExplorerTiles {
+    /// Newly visited tiles per day, in chronological order
+    pub fn new_tiles(&self) -> Vec<(&str, Vec<(u32, u32)>)> {
+        let mut days: HashMap<&str, Vec<(u32, u32)>> = HashMap::new();
+        for (tile, date) in &self.tiles {
+            days.entry(date.as_str()).or_default().push(*tile);
+        }
+        let mut days: Vec<_> = days.into_iter().collect();
+        days.sort();
+        for (_, tiles) in &mut days {
+            tiles.sort();
+        }
+        days
+    }
+
+    /// Bounding box of a tile range `[xmin, ymin, xmax, ymax]`
+    pub fn bbox(&self, (x, y): (u32, u32), size: u32) -> [f64; 4] {
+        let upper_left = TileCoord { z: self.zoom, x, y }.bbox(0.0);
+        let lower_right = TileCoord {
+            z: self.zoom,
+            x: x + size - 1,
+            y: y + size - 1,
+        }
+        .bbox(0.0);
+        [upper_left[0], lower_right[1], lower_right[2], upper_left[3]]
+    }
+}
+
+/// Collect tiles touched by the tracks of a device
+pub async fn explorer_tiles(db: &Db, params: &ExplorerParams) -> anyhow::Result<ExplorerTiles> {
+    let zoom = params.zoom();
+    let n = 1i64 << zoom;
+    let mut tiles: HashMap<(u32, u32), String> = HashMap::new();
+    let mut fixes = db.stream_fixes(params.device_id, None, None);
+    let mut prev: Option<((f64, f64), i64)> = None;
+    while let Some(pt) = fixes.next().await {
+        let pt = pt?;
+        if pt.accuracy.unwrap_or(0) >= MAX_ACCURACY {
+            continue;
+        }
+        let Some(ts) = parse_timestamp(&pt.ts).map(|dt| dt.timestamp()) else {
+            continue;
+        };
+        let pos = mvt::tile_position(zoom, pt.x, pt.y);
+        let start = segment_start(prev, (pos, ts), params.max_gap(), n);
+        let date = pt.ts.get(..10).unwrap_or_default();
+        traverse(start, pos, |x, y| {
+            if (0..n).contains(&x) && (0..n).contains(&y) {
+                tiles
+                    .entry((x as u32, y as u32))
+                    .or_insert_with(|| date.to_string());
+            }
+        });
+        prev = Some((pos, ts));
+    }
+    let visited: HashSet<(u32, u32)> = tiles.keys().copied().collect();
+    Ok(ExplorerTiles {
+        zoom,
+        max_cluster: max_cluster(&visited),
+        max_square: max_square(&visited),
+        tiles,
+    })
+}
+
+/// Start of the line to a fix: the previous fix, unless the gap is too long or the
+/// track crosses the antimeridian (more than half of the `n` tiles in x direction)
+fn segment_start(
+    prev: Option<((f64, f64), i64)>,
+    (pos, ts): ((f64, f64), i64),
+    max_gap: i64,
+    n: i64,
+) -> (f64, f64) {
+    match prev {
+        Some((prev_pos, prev_ts))
+            if ts - prev_ts <= max_gap && (pos.0 - prev_pos.0).abs() <= n as f64 / 2.0 =>
+        {
+            prev_pos
+        }
+        _ => pos,
+    }
+}
+
+/// Visit all tiles crossed by a line (Amanatides-Woo traversal)
+fn traverse(a: (f64, f64), b: (f64, f64), mut visit: impl FnMut(i64, i64)) {
+    let (mut x, mut y) = (a.0.floor() as i64, a.1.floor() as i64);
+    let (x_end, y_end) = (b.0.floor() as i64, b.1.floor() as i64);
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let axis = |d: f64, pos: f64, cell: i64| {
+        if d > 0.0 {
+            (1, ((cell + 1) as f64 - pos) / d, 1.0 / d)
+        } else if d < 0.0 {
+            (-1, (cell as f64 - pos) / d, -1.0 / d)
+        } else {
+            (0, f64::INFINITY, f64::INFINITY)
+        }
+    };
+    let (step_x, mut t_max_x, t_delta_x) = axis(dx, a.0, x);
+    let (step_y, mut t_max_y, t_delta_y) = axis(dy, a.1, y);
+    let steps = (x_end - x).abs() + (y_end - y).abs();
+    visit(x, y);
+    for _ in 0..steps {
+        if t_max_x < t_max_y {
+            x += step_x;
+            t_max_x += t_delta_x;
+        } else {
+            y += step_y;
+            t_max_y += t_delta_y;
+        }
+        visit(x, y);
+    }
+}
+
+/// Neighbours sharing an edge
+fn neighbours((x, y): (u32, u32)) -> [(u32, u32); 4] {
+    [
+        (x.wrapping_sub(1), y),
+        (x + 1, y),
+        (x, y.wrapping_sub(1)),
+        (x, y + 1),
+    ]
+}
+
+/// Largest connected set of visited tiles with all neighbours visited
+fn max_cluster(visited: &HashSet<(u32, u32)>) -> HashSet<(u32, u32)> {
+    let mut remaining: HashSet<(u32, u32)> = visited
+        .iter()
+        .filter(|tile| neighbours(**tile).iter().all(|n| visited.contains(n)))
+        .copied()
+        .collect();
+    let mut max = HashSet::new();
+    while let Some(&start) = remaining.iter().next() {
+        remaining.remove(&start);
+        let mut cluster = HashSet::from([start]);
+        let mut queue = VecDeque::from([start]);
+        while let Some(tile) = queue.pop_front() {
+            for n in neighbours(tile) {
+                if remaining.remove(&n) {
+                    cluster.insert(n);
+                    queue.push_back(n);
+                }
+            }
+        }
+        if cluster.len() > max.len() {
+            max = cluster;
+        }
+    }
+    max
+}
+
+/// Upper left tile and size of the largest square of visited tiles
+fn max_square(visited: &HashSet<(u32, u32)>) -> Option<((u32, u32), u32)> {
+    let mut tiles: Vec<(u32, u32)> = visited.iter().copied().collect();
+    // Squares extend to the lower right, which has to be processed first
+    tiles.sort_by_key(|(x, y)| std::cmp::Reverse((*y, *x)));
+    let mut sizes: HashMap<(u32, u32), u32> = HashMap::with_capacity(tiles.len());
+    let mut max: Option<((u32, u32), u32)> = None;
+    for (x, y) in tiles {
+        let size_at = |tile| sizes.get(&tile).copied().unwrap_or(0);
+        let size = 1 + size_at((x + 1, y))
+            .min(size_at((x, y + 1)))
+            .min(size_at((x + 1, y + 1)));
+        sizes.insert((x, y), size);
+        if max.is_none_or(|(_, max_size)| size > max_size) {
+            max = Some(((x, y), size));
+        }
+    }
+    max
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn traversed(a: (f64, f64), b: (f64, f64)) -> Vec<(i64, i64)> {
+        let mut tiles = Vec::new();
+        traverse(a, b, |x, y| tiles.push((x, y)));
+        tiles
+    }
+
+    #[test]
+    fn traverse_diagonal() {
+        assert_eq!(
+            traversed((0.5, 0.5), (2.5, 1.7)),
+            [(0, 0), (1, 0), (1, 1), (2, 1)]
+        );
+        assert_eq!(
+            traversed((2.5, 1.7), (0.5, 0.5)),
+            [(2, 1), (1, 1), (1, 0), (0, 0)]
+        );
+        assert_eq!(traversed((3.2, 3.7), (3.9, 3.1)), [(3, 3)]);
+    }
+
+    #[test]
+    fn square_block() {
+        let visited: HashSet<(u32, u32)> = (10..13)
+            .flat_map(|x| (20..23).map(move |y| (x, y)))
+            .collect();
+        assert_eq!(max_cluster(&visited), HashSet::from([(11, 21)]));
+        assert_eq!(max_square(&visited), Some(((10, 20), 3)));
+        assert!(max_cluster(&HashSet::from([(0, 0)])).is_empty());
+        assert_eq!(max_square(&HashSet::new()), None);
+    }
+
+    #[test]
+    fn antimeridian_jump() {
+        let n = 1 << 10;
+        let prev = Some(((1023.9, 400.0), 0));
+        assert_eq!(
+            segment_start(prev, ((0.1, 400.0), 60), 600, n),
+            (0.1, 400.0)
+        );
+        assert_eq!(
+            segment_start(prev, ((1020.0, 401.0), 60), 600, n),
+            (1023.9, 400.0)
+        );
+        // Gap too long
+        assert_eq!(
+            segment_start(prev, ((1020.0, 401.0), 700), 600, n),
+            (1020.0, 401.0)
+        );
+    }
+}
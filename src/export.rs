@@ -0,0 +1,285 @@
+use crate::csv_export;
+use crate::db::{day_range, Db, GpsPoint};
+use crate::geojsonseq;
+use crate::geoparquet;
+use actix_web::web::Bytes;
+use futures_util::stream::{self, Stream};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::io::Write;
+use std::path::PathBuf;
+use tokio::sync::mpsc;
+
+/// Number of buffered chunks between exporter and consumer
+const CHANNEL_CAPACITY: usize = 4;
+/// Prefix of columns with the value of a single annotation key
+const ANNOTATION_PREFIX: &str = "annotations.";
+
+/// Fixes selected for export
+#[derive(clap::Args, Deserialize, Clone, Debug)]
+pub struct ExportSelection {
+    #[arg(long)]
+    pub device_id: i32,
+    /// First date in format 2025-02-19
+    #[arg(long)]
+    pub date_from: Option<String>,
+    /// Last date in format 2025-02-19
+    #[arg(long)]
+    pub date_to: Option<String>,
+    /// Comma separated list of columns, `annotations.<key>` for single annotations (CSV and GeoJSONSeq only)
+    #[arg(long)]
+    pub columns: Option<String>,
+}
+
+impl ExportSelection {
+    /// Selected columns or the given default columns
+    pub fn columns(&self, default: &str) -> anyhow::Result<Vec<Column>> {
+        self.columns
+            .as_deref()
+            .unwrap_or(default)
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(Column::from_name)
+            .collect()
+    }
+
+    /// Check the dates and the selected columns
+    pub fn validate(&self) -> anyhow::Result<()> {
+        for date in [&self.date_from, &self.date_to].into_iter().flatten() {
+            if day_range(date).is_none() {
+                anyhow::bail!("Invalid date '{date}'");
+            }
+        }
+        self.columns("").map(|_| ())
+    }
+}
+
+/// Exported attribute of a fix
+#[derive(Clone, Debug, PartialEq)]
+pub enum Column {
+    Ts,
+    Lat,
+    Lon,
+    Tid,
+    Speed,
+    Elevation,
+    Accuracy,
+    VAccuracy,
+    Cog,
+    /// All annotations
+    Annotations,
+    /// Value of a single annotation key, selected as `annotations.<key>`
+    Annotation(String),
+}
+
+impl Column {
+    /// Known column or annotation key with prefix `annotations.`
+    fn from_name(name: &str) -> anyhow::Result<Self> {
+        let column = match name {
+            "ts" => Column::Ts,
+            "lat" => Column::Lat,
+            "lon" => Column::Lon,
+            "tid" => Column::Tid,
+            "speed" => Column::Speed,
+            "elevation" => Column::Elevation,
+            "accuracy" => Column::Accuracy,
+            "v_accuracy" => Column::VAccuracy,
+            "cog" => Column::Cog,
+            "annotations" => Column::Annotations,
+            name => match name.strip_prefix(ANNOTATION_PREFIX) {
+                Some(key) if !key.is_empty() => Column::Annotation(key.to_string()),
+                _ => anyhow::bail!("Unknown column `{name}`"),
+            },
+        };
+        Ok(column)
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            Column::Ts => "ts",
+            Column::Lat => "lat",
+            Column::Lon => "lon",
+            Column::Tid => "tid",
+            Column::Speed => "speed",
+            Column::Elevation => "elevation",
+            Column::Accuracy => "accuracy",
+            Column::VAccuracy => "v_accuracy",
+            Column::Cog => "cog",
+            Column::Annotations => "annotations",
+            Column::Annotation(key) => key,
+        }
+    }
+
+    /// Column value of a fix with its parsed annotations
+    pub fn value(&self, pt: &GpsPoint, annotations: &Map<String, Value>) -> Value {
+        match self {
+            Column::Ts => Value::from(pt.rfc3339()),
+            Column::Lat => Value::from(pt.y),
+            Column::Lon => Value::from(pt.x),
+            Column::Tid => Value::from(pt.tid.as_str()),
+            Column::Speed => Value::from(pt.speed),
+            Column::Elevation => Value::from(pt.elevation),
+            Column::Accuracy => Value::from(pt.accuracy),
+            Column::VAccuracy => Value::from(pt.v_accuracy),
+            Column::Cog => Value::from(pt.cog),
+            Column::Annotations => Value::Object(annotations.clone()),
+            Column::Annotation(key) => annotations.get(key).cloned().unwrap_or(Value::Null),
+        }
+    }
+}
+
+/// Annotations of a fix without OwnTracks message metadata like `_type` and `_id`
+pub fn annotations(pt: &GpsPoint) -> Map<String, Value> {
+    let mut annotations: Map<String, Value> =
+        serde_json::from_str(&pt.annotations).unwrap_or_default();
+    annotations.retain(|key, _| !key.starts_with('_'));
+    annotations
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+pub enum ExportFormat {
+    /// GeoParquet
+    Parquet,
+    /// Comma separated values
+    Csv,
+    /// GeoJSON text sequence (RFC 8142)
+    #[value(name = "geojsonseq")]
+    GeoJsonSeq,
+}
+
+impl ExportFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Parquet => "application/vnd.apache.parquet",
+            ExportFormat::Csv => "text/csv",
+            ExportFormat::GeoJsonSeq => "application/geo+json-seq",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Parquet => "parquet",
+            ExportFormat::Csv => "csv",
+            ExportFormat::GeoJsonSeq => "geojsons",
+        }
+    }
+}
+
+#[derive(clap::Args, Debug)]
+pub struct ExportArgs {
+    #[command(flatten)]
+    pub selection: ExportSelection,
+    #[arg(long, value_enum, default_value_t = ExportFormat::Parquet)]
+    pub format: ExportFormat,
+    /// Output file
+    pub output: PathBuf,
+}
+
+/// Write exported data in chunks to a channel
+async fn write_chunks(
+    db: &Db,
+    selection: &ExportSelection,
+    format: ExportFormat,
+    tx: mpsc::Sender<Vec<u8>>,
+) -> anyhow::Result<()> {
+    match format {
+        ExportFormat::Parquet => geoparquet::write(db, selection, tx).await,
+        ExportFormat::Csv => csv_export::write(db, selection, tx).await,
+        ExportFormat::GeoJsonSeq => geojsonseq::write(db, selection, tx).await,
+    }
+}
+
+/// Export fixes into a file
+pub async fn export(db: &Db, args: &ExportArgs) -> anyhow::Result<()> {
+    args.selection.validate()?;
+    let mut file = std::io::BufWriter::new(std::fs::File::create(&args.output)?);
+    let (tx, mut rx) = mpsc::channel(CHANNEL_CAPACITY);
+    let (result, written) =
+        tokio::join!(write_chunks(db, &args.selection, args.format, tx), async {
+            while let Some(chunk) = rx.recv().await {
+                file.write_all(&chunk)?;
+            }
+            file.flush()
+        });
+    result?;
+    written?;
+    log::info!("Exported fixes to `{}`", args.output.display());
+    Ok(())
+}
+
+/// Stream exported fixes as chunks of bytes
+pub fn export_stream(
+    db: Db,
+    selection: ExportSelection,
+    format: ExportFormat,
+) -> impl Stream<Item = Result<Bytes, actix_web::Error>> {
+    let (tx, rx) = mpsc::channel(CHANNEL_CAPACITY);
+    let (err_tx, err_rx) = tokio::sync::oneshot::channel();
+    actix_web::rt::spawn(async move {
+        if let Err(e) = write_chunks(&db, &selection, format, tx).await {
+            log::error!("Export failed: {e}");
+            let _ = err_tx.send(e);
+        }
+    });
+    stream::unfold((rx, Some(err_rx)), |(mut rx, err_rx)| async move {
+        match rx.recv().await {
+            Some(chunk) => Some((Ok(Bytes::from(chunk)), (rx, err_rx))),
+            // Abort the response if the export failed
+            None => match err_rx?.await {
+                Ok(e) => Some((
+                    Err(actix_web::error::ErrorInternalServerError(e)),
+                    (rx, None),
+                )),
+                Err(_) => None,
+            },
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn selection(columns: &str) -> ExportSelection {
+        ExportSelection {
+            device_id: 1,
+            date_from: None,
+            date_to: None,
+            columns: Some(columns.to_string()),
+        }
+    }
+
+    #[test]
+    fn known_columns() {
+        let columns = selection("ts, lat,annotations,annotations.batt")
+            .columns("")
+            .unwrap();
+        assert_eq!(
+            columns,
+            [
+                Column::Ts,
+                Column::Lat,
+                Column::Annotations,
+                Column::Annotation("batt".to_string())
+            ]
+        );
+        assert_eq!(columns[3].name(), "batt");
+    }
+
+    #[test]
+    fn unknown_columns() {
+        assert!(selection("ts,batt").validate().is_err());
+        assert!(selection("annotations.").validate().is_err());
+        assert!(selection("").validate().is_ok());
+    }
+
+    #[test]
+    fn invalid_dates() {
+        let mut selection = selection("");
+        selection.date_from = Some("2025-10-09".to_string());
+        assert!(selection.validate().is_ok());
+        selection.date_to = Some("2025-10-32".to_string());
+        assert!(selection.validate().is_err());
+    }
+}
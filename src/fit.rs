@@ -0,0 +1,357 @@
+//! Garmin FIT activity file encoder.
+
+use crate::activity::{self, ActivityType, Sample};
+use crate::db::TrackData;
+
+/// Seconds between Unix epoch and FIT epoch (1989-12-31 00:00:00 UTC)
+const FIT_EPOCH_OFFSET: i64 = 631065600;
+const PROTOCOL_VERSION: u8 = 0x10;
+const PROFILE_VERSION: u16 = 2132;
+
+// Base types
+const ENUM: u8 = 0x00;
+const UINT16: u8 = 0x84;
+const SINT32: u8 = 0x85;
+const UINT32: u8 = 0x86;
+
+// Global message numbers
+const MESG_FILE_ID: u16 = 0;
+const MESG_SESSION: u16 = 18;
+const MESG_LAP: u16 = 19;
+const MESG_RECORD: u16 = 20;
+const MESG_EVENT: u16 = 21;
+const MESG_ACTIVITY: u16 = 34;
+
+// Local message types
+const LOCAL_FILE_ID: u8 = 0;
+const LOCAL_EVENT: u8 = 1;
+const LOCAL_RECORD: u8 = 2;
+const LOCAL_LAP: u8 = 3;
+const LOCAL_SESSION: u8 = 4;
+const LOCAL_ACTIVITY: u8 = 5;
+
+// Enum values
+const FILE_ACTIVITY: u8 = 4;
+const MANUFACTURER_DEVELOPMENT: u16 = 255;
+const EVENT_TIMER: u8 = 0;
+const EVENT_SESSION: u8 = 8;
+const EVENT_LAP: u8 = 9;
+const EVENT_ACTIVITY: u8 = 26;
+const EVENT_TYPE_START: u8 = 0;
+const EVENT_TYPE_STOP: u8 = 1;
+const EVENT_TYPE_STOP_ALL: u8 = 4;
+
+fn sport(activity: ActivityType) -> u8 {
+    match activity {
+        ActivityType::Running => 1,
+        ActivityType::Cycling => 2,
+        ActivityType::Walking => 11,
+        ActivityType::Hiking => 17,
+        ActivityType::Other => 0,
+    }
+}
+
+/// CRC-16 as specified by the FIT protocol
+fn crc(data: &[u8]) -> u16 {
+    const TABLE: [u16; 16] = [
+        0x0000, 0xCC01, 0xD801, 0x1400, 0xF001, 0x3C00, 0x2800, 0xE401, 0xA001, 0x6C00, 0x7800,
+        0xB401, 0x5000, 0x9C01, 0x8801, 0x4400,
+    ];
+    data.iter().fold(0, |mut crc, byte| {
+        for nibble in [byte & 0xF, byte >> 4] {
+            let tmp = TABLE[(crc & 0xF) as usize];
+            crc = (crc >> 4) & 0x0FFF;
+            crc = crc ^ tmp ^ TABLE[nibble as usize];
+        }
+        crc
+    })
+}
+
+fn timestamp(ts: i64) -> u32 {
+    (ts - FIT_EPOCH_OFFSET).max(0) as u32
+}
+
+/// Degrees in semicircles
+fn semicircles(deg: f64) -> i32 {
+    (deg * (2f64.powi(31) / 180.0)) as i32
+}
+
+/// Scaled value, `u32::MAX` is the invalid value
+fn scaled_u32(value: f64, scale: f64) -> u32 {
+    (value * scale).round().clamp(0.0, (u32::MAX - 1) as f64) as u32
+}
+
+/// Scaled value, `u16::MAX` is the invalid value
+fn scaled_u16(value: Option<f64>, scale: f64, offset: f64) -> u16 {
+    value
+        .map(|value| {
+            ((value + offset) * scale)
+                .round()
+                .clamp(0.0, (u16::MAX - 1) as f64) as u16
+        })
+        .unwrap_or(u16::MAX)
+}
+
+/// Writer for definition and data messages
+#[derive(Default)]
+struct FitWriter {
+    data: Vec<u8>,
+}
+
+impl FitWriter {
+    /// Write definition message with fields (field number, size, base type)
+    fn define(&mut self, local: u8, global: u16, fields: &[(u8, u8, u8)]) {
+        self.data.extend([0x40 | local, 0, 0]);
+        self.data.extend(global.to_le_bytes());
+        self.data.push(fields.len() as u8);
+        for (num, size, base_type) in fields {
+            self.data.extend([*num, *size, *base_type]);
+        }
+    }
+
+    /// Start data message
+    fn message(&mut self, local: u8) -> &mut Self {
+        self.data.push(local);
+        self
+    }
+
+    fn u8(&mut self, value: u8) -> &mut Self {
+        self.data.push(value);
+        self
+    }
+
+    fn u16(&mut self, value: u16) -> &mut Self {
+        self.data.extend(value.to_le_bytes());
+        self
+    }
+
+    fn u32(&mut self, value: u32) -> &mut Self {
+        self.data.extend(value.to_le_bytes());
+        self
+    }
+
+    fn i32(&mut self, value: i32) -> &mut Self {
+        self.data.extend(value.to_le_bytes());
+        self
+    }
+
+    /// File with header and CRC
+    fn finish(self) -> Vec<u8> {
+        let mut file = Vec::with_capacity(self.data.len() + 16);
+        file.extend([14, PROTOCOL_VERSION]);
+        file.extend(PROFILE_VERSION.to_le_bytes());
+        file.extend((self.data.len() as u32).to_le_bytes());
+        file.extend(b".FIT");
+        file.extend(crc(&file).to_le_bytes());
+        file.extend(self.data);
+        file.extend(crc(&file).to_le_bytes());
+        file
+    }
+}
+
+/// Write summary message fields shared by laps and sessions
+fn write_summary(fit: &mut FitWriter, start: &Sample, end: &Sample, timer_time: f64) {
+    fit.u32(timestamp(end.ts))
+        .u32(timestamp(start.ts))
+        .u32(scaled_u32((end.ts - start.ts) as f64, 1000.0))
+        .u32(scaled_u32(timer_time, 1000.0))
+        .u32(scaled_u32(end.distance - start.distance, 100.0));
+}
+
+/// Build a FIT activity file with one lap per track.
+pub fn tracks(tracks: &[TrackData], activity: ActivityType) -> anyhow::Result<Vec<u8>> {
+    let sport = sport(activity);
+    let laps: Vec<Vec<Sample>> = tracks
+        .iter()
+        .map(activity::samples)
+        .filter(|samples| !samples.is_empty())
+        .collect();
+    let mut fit = FitWriter::default();
+
+    fit.define(
+        LOCAL_FILE_ID,
+        MESG_FILE_ID,
+        &[(0, 1, ENUM), (1, 2, UINT16), (2, 2, UINT16), (4, 4, UINT32)],
+    );
+    let time_created = laps.first().map_or(0, |samples| timestamp(samples[0].ts));
+    fit.message(LOCAL_FILE_ID)
+        .u8(FILE_ACTIVITY)
+        .u16(MANUFACTURER_DEVELOPMENT)
+        .u16(0)
+        .u32(time_created);
+
+    let (Some(first), Some(last)) = (
+        laps.first().and_then(|samples| samples.first()),
+        laps.last().and_then(|samples| samples.last()),
+    ) else {
+        return Ok(fit.finish());
+    };
+
+    fit.define(
+        LOCAL_EVENT,
+        MESG_EVENT,
+        &[(253, 4, UINT32), (0, 1, ENUM), (1, 1, ENUM)],
+    );
+    fit.define(
+        LOCAL_RECORD,
+        MESG_RECORD,
+        &[
+            (253, 4, UINT32),
+            (0, 4, SINT32),
+            (1, 4, SINT32),
+            (2, 2, UINT16),
+            (5, 4, UINT32),
+            (6, 2, UINT16),
+        ],
+    );
+    fit.define(
+        LOCAL_LAP,
+        MESG_LAP,
+        &[
+            (253, 4, UINT32),
+            (2, 4, UINT32),
+            (7, 4, UINT32),
+            (8, 4, UINT32),
+            (9, 4, UINT32),
+            (0, 1, ENUM),
+            (1, 1, ENUM),
+            (25, 1, ENUM),
+        ],
+    );
+
+    fit.message(LOCAL_EVENT)
+        .u32(timestamp(first.ts))
+        .u8(EVENT_TIMER)
+        .u8(EVENT_TYPE_START);
+    // Distances are cumulated over all laps
+    let mut distance_offset = 0.0;
+    let mut timer_time = 0.0;
+    for samples in &laps {
+        for sample in samples {
+            fit.message(LOCAL_RECORD)
+                .u32(timestamp(sample.ts))
+                .i32(semicircles(sample.y))
+                .i32(semicircles(sample.x))
+                .u16(scaled_u16(sample.altitude, 5.0, 500.0))
+                .u32(scaled_u32(distance_offset + sample.distance, 100.0))
+                .u16(scaled_u16(sample.speed, 1000.0, 0.0));
+        }
+        let (start, end) = (&samples[0], &samples[samples.len() - 1]);
+        let lap_time = (end.ts - start.ts) as f64;
+        fit.message(LOCAL_LAP);
+        write_summary(&mut fit, start, end, lap_time);
+        fit.u8(EVENT_LAP).u8(EVENT_TYPE_STOP).u8(sport);
+        distance_offset += end.distance;
+        timer_time += lap_time;
+    }
+    fit.message(LOCAL_EVENT)
+        .u32(timestamp(last.ts))
+        .u8(EVENT_TIMER)
+        .u8(EVENT_TYPE_STOP_ALL);
+
+    fit.define(
+        LOCAL_SESSION,
+        MESG_SESSION,
+        &[
+            (253, 4, UINT32),
+            (2, 4, UINT32),
+            (7, 4, UINT32),
+            (8, 4, UINT32),
+            (9, 4, UINT32),
+            (0, 1, ENUM),
+            (1, 1, ENUM),
+            (5, 1, ENUM),
+            (6, 1, ENUM),
+            (25, 2, UINT16),
+            (26, 2, UINT16),
+        ],
+    );
+    let session_end = Sample {
+        distance: distance_offset,
+        ..*last
+    };
+    let session_start = Sample {
+        distance: 0.0,
+        ..*first
+    };
+    fit.message(LOCAL_SESSION);
+    write_summary(&mut fit, &session_start, &session_end, timer_time);
+    fit.u8(EVENT_SESSION)
+        .u8(EVENT_TYPE_STOP)
+        .u8(sport)
+        .u8(0)
+        .u16(0)
+        .u16(laps.len() as u16);
+
+    fit.define(
+        LOCAL_ACTIVITY,
+        MESG_ACTIVITY,
+        &[
+            (253, 4, UINT32),
+            (0, 4, UINT32),
+            (1, 2, UINT16),
+            (2, 1, ENUM),
+            (3, 1, ENUM),
+            (4, 1, ENUM),
+        ],
+    );
+    fit.message(LOCAL_ACTIVITY)
+        .u32(timestamp(last.ts))
+        .u32(scaled_u32(timer_time, 1000.0))
+        .u16(1)
+        .u8(0)
+        .u8(EVENT_ACTIVITY)
+        .u8(EVENT_TYPE_STOP);
+
+    Ok(fit.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::GpsPoint;
+
+    #[test]
+    fn crc_check_value() {
+        // CRC-16/ARC check value
+        assert_eq!(crc(b"123456789"), 0xBB3D);
+        assert_eq!(crc(&[]), 0);
+    }
+
+    #[test]
+    fn header_and_crc() {
+        let track = TrackData {
+            device_id: 1,
+            date: "2025-10-09".to_string(),
+            points: (0..10)
+                .map(|i| GpsPoint {
+                    x: 9.43 + i as f64 * 0.0002,
+                    y: 47.05,
+                    ts: format!("2025-10-09 08:00:{:02}", i * 5),
+                    elevation: Some(500),
+                    ..Default::default()
+                })
+                .collect(),
+        };
+        let file = tracks(&[track], ActivityType::Walking).unwrap();
+        assert_eq!(file[0], 14);
+        assert_eq!(file[1], PROTOCOL_VERSION);
+        assert_eq!(u16::from_le_bytes([file[2], file[3]]), PROFILE_VERSION);
+        let data_size = u32::from_le_bytes(file[4..8].try_into().unwrap()) as usize;
+        assert_eq!(data_size, file.len() - 16);
+        assert_eq!(&file[8..12], b".FIT");
+        assert_eq!(crc(&file[..12]), u16::from_le_bytes([file[12], file[13]]));
+        // CRC over the whole file including its trailing CRC is zero
+        assert_eq!(crc(&file), 0);
+        // File id definition follows the header
+        assert_eq!(file[14], 0x40 | LOCAL_FILE_ID);
+    }
+
+    #[test]
+    fn units() {
+        assert_eq!(timestamp(FIT_EPOCH_OFFSET + 10), 10);
+        assert_eq!(timestamp(0), 0);
+        assert_eq!(semicircles(90.0), 1 << 30);
+        assert_eq!(semicircles(-180.0), i32::MIN);
+    }
+}
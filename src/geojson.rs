@@ -1,8 +1,14 @@
-use crate::db::{GpsPoint, Position, TrackData};
+use crate::db::{GpsPoint, Position, TrackData, TrackInfo};
+use crate::encounters::Encounter;
+use crate::explorer::ExplorerTiles;
+use crate::heatmap::HeatmapCell;
+use crate::interpolate::DevicePosition;
+use crate::splits;
 use crate::stats::{BboxStats, DistanceStats, ElevationDiffStats, TrackStats};
+use chrono::DateTime;
 use geojson::{Feature, FeatureCollection, Geometry, JsonObject, JsonValue};
 
-const MAX_ACCURACY: i32 = 200; // meters
+pub const MAX_ACCURACY: i32 = 200; // meters
 const ANNOTATIONS_SKIP_LIST: &[&str] = &["_id", "m", "BSSID", "SSID", "created_at"];
 
 fn point_properties(pt: &GpsPoint) -> JsonObject {
@@ -30,7 +36,7 @@ pub fn track(tracks: &[TrackData]) -> anyhow::Result<String> {
     let features: Vec<Feature> = tracks
         .iter()
         .map(|track| {
-            let points = track.points.iter().filter(|point| {
+            let mut points = track.points.iter().filter(|point| {
                 // keep only points within accuracy
                 point.accuracy.unwrap_or(0) < MAX_ACCURACY
             });
@@ -39,7 +45,7 @@ pub fn track(tracks: &[TrackData]) -> anyhow::Result<String> {
             ));
             let bbox = BboxStats::from_xy_iter(points.clone().map(|pt| (pt.x, pt.y))).bbox();
             // Use properties of last point
-            let properties = points.last().map(point_properties);
+            let properties = points.next_back().map(point_properties);
             Feature {
                 geometry: Some(geometry),
                 properties,
@@ -94,8 +100,14 @@ pub fn track_with_segments(tracks: &[TrackData]) -> anyhow::Result<String> {
     Ok(geojson.to_string())
 }
 
-/// Build a GeoJSON Point FeatureCollection
-pub fn track_points(tracks: &[TrackData]) -> anyhow::Result<String> {
+/// Build a GeoJSON Point FeatureCollection with track statistics
+///
+/// `distance` overrides the computed track length (e.g. from PostGIS).
+pub fn track_points(
+    tracks: &[TrackData],
+    distance: Option<f64>,
+    split_distance: f64,
+) -> anyhow::Result<String> {
     let feat_iter = tracks
         .iter()
         .flat_map(|track| track.points.iter())
@@ -121,11 +133,23 @@ pub fn track_points(tracks: &[TrackData]) -> anyhow::Result<String> {
     let bbox = BboxStats::from_xy_iter(feat_iter.clone().map(|pt| (pt.x, pt.y))).bbox();
 
     let mut stats = TrackStats::from_iter(feat_iter.clone()).as_properties();
-    stats.extend(
-        ElevationDiffStats::from_iter(feat_iter.clone().filter_map(|pt| pt.elevation))
-            .as_properties(),
-    );
-    stats.extend(DistanceStats::from_xy_iter(feat_iter.map(|pt| (pt.x, pt.y))).as_properties());
+    stats.extend(ElevationDiffStats::from_points(feat_iter.clone()).as_properties());
+    let distance_stats = match distance {
+        Some(distance) => DistanceStats::from_distance(distance),
+        None => DistanceStats::from_xy_iter(feat_iter.clone().map(|pt| (pt.x, pt.y))),
+    };
+    stats.extend(distance_stats.as_properties());
+    let points: Vec<&GpsPoint> = feat_iter.collect();
+    stats.extend([
+        (
+            "splits".to_string(),
+            serde_json::to_value(splits::splits(&points, split_distance))?,
+        ),
+        (
+            "laps".to_string(),
+            serde_json::to_value(splits::laps(&points))?,
+        ),
+    ]);
     let stats_json = JsonObject::from_iter([("stats".to_string(), JsonValue::from(stats))]);
 
     let geojson = FeatureCollection {
@@ -171,3 +195,256 @@ pub fn positions(points: &[Position]) -> anyhow::Result<String> {
     };
     Ok(geojson.to_string())
 }
+
+/// Build a GeoJSON Point FeatureCollection of interpolated device positions.
+///
+/// Positions without nearby fix have no geometry.
+pub fn interpolated_positions(positions: &[DevicePosition]) -> anyhow::Result<String> {
+    Ok(interpolated_feature_collection(positions).to_string())
+}
+
+fn interpolated_feature_collection(positions: &[DevicePosition]) -> FeatureCollection {
+    let features = positions
+        .iter()
+        .map(|(device_id, ts, pos)| {
+            let time = DateTime::from_timestamp(*ts, 0)
+                .map(|dt| dt.format("%F %T%z").to_string())
+                .unwrap_or_default();
+            let mut properties = JsonObject::from_iter([
+                ("device_id".to_string(), JsonValue::from(*device_id)),
+                ("time".to_string(), JsonValue::from(time)),
+            ]);
+            let geometry = pos.as_ref().map(|pos| {
+                properties.extend([
+                    ("tid".to_string(), JsonValue::from(pos.tid.clone())),
+                    ("elevation".to_string(), JsonValue::from(pos.elevation)),
+                    ("gap".to_string(), JsonValue::from(pos.gap)),
+                    ("uncertainty".to_string(), JsonValue::from(pos.uncertainty)),
+                    (
+                        "interpolated".to_string(),
+                        JsonValue::from(pos.interpolated),
+                    ),
+                ]);
+                Geometry::new(geojson::Value::Point(vec![pos.x, pos.y]))
+            });
+            Feature {
+                geometry,
+                properties: Some(properties),
+                ..Default::default()
+            }
+        })
+        .collect();
+    let bbox = BboxStats::from_xy_iter(
+        positions
+            .iter()
+            .filter_map(|(_, _, pos)| pos.as_ref().map(|pos| (pos.x, pos.y))),
+    )
+    .bbox();
+
+    FeatureCollection {
+        features,
+        bbox,
+        ..Default::default()
+    }
+}
+
+/// Build JSON with a GeoJSON FeatureCollection of interpolated device positions per frame
+pub fn playback(frames: &[(i64, Vec<DevicePosition>)]) -> anyhow::Result<String> {
+    let frames: Vec<JsonValue> = frames
+        .iter()
+        .map(|(ts, positions)| {
+            let mut fc = interpolated_feature_collection(positions);
+            let time = DateTime::from_timestamp(*ts, 0)
+                .map(|dt| dt.format("%F %T%z").to_string())
+                .unwrap_or_default();
+            fc.foreign_members = Some(JsonObject::from_iter([(
+                "time".to_string(),
+                JsonValue::from(time),
+            )]));
+            JsonValue::Object(JsonObject::from(&fc))
+        })
+        .collect();
+    let json = JsonObject::from_iter([("frames".to_string(), JsonValue::from(frames))]);
+    Ok(JsonValue::from(json).to_string())
+}
+
+/// Build a GeoJSON Point FeatureCollection of encounters between devices
+pub fn encounters(encounters: &[Encounter], devices: &[TrackInfo]) -> anyhow::Result<String> {
+    let features = encounters
+        .iter()
+        .map(|encounter| {
+            let geometry = Geometry::new(geojson::Value::Point(vec![encounter.x, encounter.y]));
+            let participants: Vec<JsonValue> = encounter
+                .device_ids
+                .iter()
+                .map(|device_id| {
+                    let info = devices.iter().find(|info| info.device_id == *device_id);
+                    serde_json::json!({
+                        "device_id": device_id,
+                        "user_id": info.map(|info| info.user_id.clone()),
+                        "device": info.map(|info| info.device.clone()),
+                        "tid": info.map(|info| info.tid.clone()),
+                    })
+                })
+                .collect();
+            let time = |ts| {
+                DateTime::from_timestamp(ts, 0)
+                    .map(|dt| dt.format("%F %T%z").to_string())
+                    .unwrap_or_default()
+            };
+            let properties = JsonObject::from_iter([
+                ("participants".to_string(), JsonValue::from(participants)),
+                (
+                    "ts_start".to_string(),
+                    JsonValue::from(time(encounter.ts_start)),
+                ),
+                (
+                    "ts_end".to_string(),
+                    JsonValue::from(time(encounter.ts_end)),
+                ),
+                (
+                    "duration".to_string(),
+                    JsonValue::from(encounter.ts_end - encounter.ts_start),
+                ),
+                (
+                    "min_distance".to_string(),
+                    JsonValue::from(encounter.min_distance),
+                ),
+            ]);
+            Feature {
+                geometry: Some(geometry),
+                properties: Some(properties),
+                ..Default::default()
+            }
+        })
+        .collect();
+    let bbox = BboxStats::from_xy_iter(encounters.iter().map(|e| (e.x, e.y))).bbox();
+
+    let geojson = FeatureCollection {
+        features,
+        bbox,
+        ..Default::default()
+    };
+    Ok(geojson.to_string())
+}
+
+/// Heatmap cells as GeoJSON polygons with the share of the maximal duration as `weight`
+pub fn heatmap(cells: &[HeatmapCell]) -> anyhow::Result<String> {
+    let max_duration = cells
+        .iter()
+        .map(|cell| cell.duration)
+        .max()
+        .unwrap_or(0)
+        .max(1);
+    let features = cells
+        .iter()
+        .map(|cell| {
+            let ring = cell.polygon.iter().map(|(x, y)| vec![*x, *y]).collect();
+            let geometry = Geometry::new(geojson::Value::Polygon(vec![ring]));
+            let properties = JsonObject::from_iter([
+                ("duration".to_string(), JsonValue::from(cell.duration)),
+                ("count".to_string(), JsonValue::from(cell.count)),
+                (
+                    "weight".to_string(),
+                    JsonValue::from(cell.duration as f64 / max_duration as f64),
+                ),
+            ]);
+            Feature {
+                geometry: Some(geometry),
+                properties: Some(properties),
+                ..Default::default()
+            }
+        })
+        .collect();
+    let bbox =
+        BboxStats::from_xy_iter(cells.iter().flat_map(|cell| cell.polygon.iter().copied())).bbox();
+
+    let geojson = FeatureCollection {
+        features,
+        bbox,
+        ..Default::default()
+    };
+    Ok(geojson.to_string())
+}
+
+fn tile_polygon(bbox: [f64; 4]) -> Geometry {
+    let [xmin, ymin, xmax, ymax] = bbox;
+    let ring = vec![
+        vec![xmin, ymin],
+        vec![xmax, ymin],
+        vec![xmax, ymax],
+        vec![xmin, ymax],
+        vec![xmin, ymin],
+    ];
+    Geometry::new(geojson::Value::Polygon(vec![ring]))
+}
+
+pub fn explorer(explorer: &ExplorerTiles) -> anyhow::Result<String> {
+    let mut tiles: Vec<_> = explorer.tiles.iter().collect();
+    tiles.sort();
+    let mut features: Vec<Feature> = tiles
+        .into_iter()
+        .map(|(tile, date)| {
+            let properties = JsonObject::from_iter([
+                ("kind".to_string(), JsonValue::from("tile")),
+                ("x".to_string(), JsonValue::from(tile.0)),
+                ("y".to_string(), JsonValue::from(tile.1)),
+                ("date".to_string(), JsonValue::from(date.as_str())),
+                (
+                    "cluster".to_string(),
+                    JsonValue::from(explorer.max_cluster.contains(tile)),
+                ),
+            ]);
+            Feature {
+                geometry: Some(tile_polygon(explorer.bbox(*tile, 1))),
+                properties: Some(properties),
+                ..Default::default()
+            }
+        })
+        .collect();
+    if let Some((tile, size)) = explorer.max_square {
+        let properties = JsonObject::from_iter([
+            ("kind".to_string(), JsonValue::from("max_square")),
+            ("x".to_string(), JsonValue::from(tile.0)),
+            ("y".to_string(), JsonValue::from(tile.1)),
+            ("size".to_string(), JsonValue::from(size)),
+        ]);
+        features.push(Feature {
+            geometry: Some(tile_polygon(explorer.bbox(tile, size))),
+            properties: Some(properties),
+            ..Default::default()
+        });
+    }
+    let new_tiles: Vec<JsonValue> = explorer
+        .new_tiles()
+        .into_iter()
+        .map(|(date, tiles)| {
+            serde_json::json!({
+                "date": date,
+                "count": tiles.len(),
+                "tiles": tiles,
+            })
+        })
+        .collect();
+    let stats = serde_json::json!({
+        "zoom": explorer.zoom,
+        "tiles": explorer.tiles.len(),
+        "max_cluster": explorer.max_cluster.len(),
+        "max_square": explorer.max_square.map(|(_, size)| size).unwrap_or(0),
+    });
+    let bbox = BboxStats::from_xy_iter(explorer.tiles.keys().flat_map(|tile| {
+        let [xmin, ymin, xmax, ymax] = explorer.bbox(*tile, 1);
+        [(xmin, ymin), (xmax, ymax)]
+    }))
+    .bbox();
+
+    let geojson = FeatureCollection {
+        features,
+        bbox,
+        foreign_members: Some(JsonObject::from_iter([
+            ("stats".to_string(), stats),
+            ("new_tiles".to_string(), JsonValue::from(new_tiles)),
+        ])),
+    };
+    Ok(geojson.to_string())
+}
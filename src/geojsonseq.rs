@@ -0,0 +1,133 @@
+use crate::db::{Db, GpsPoint};
+use crate::export::{self, Column, ExportSelection};
+use futures_util::stream::BoxStream;
+use futures_util::StreamExt;
+use serde_json::{json, Map};
+use tokio::sync::mpsc;
+
+/// Default feature properties
+const DEFAULT_COLUMNS: &str = "ts,tid,speed,elevation,accuracy,v_accuracy,cog,annotations";
+/// Number of features sent as one chunk
+const CHUNK_SIZE: usize = 10000;
+/// Record separator preceding each GeoJSON text
+const RS: u8 = 0x1e;
+
+/// Stream fixes as GeoJSON text sequence (RFC 8142) of Point features
+pub async fn write(
+    db: &Db,
+    selection: &ExportSelection,
+    tx: mpsc::Sender<Vec<u8>>,
+) -> anyhow::Result<()> {
+    let columns = selection.columns(DEFAULT_COLUMNS)?;
+    let fixes = db.stream_fixes(
+        selection.device_id,
+        selection.date_from.as_deref(),
+        selection.date_to.as_deref(),
+    );
+    write_fixes(&columns, fixes, tx).await
+}
+
+/// Write fixes as GeoJSON text sequence in chunks to a channel
+async fn write_fixes(
+    columns: &[Column],
+    mut fixes: BoxStream<'_, anyhow::Result<GpsPoint>>,
+    tx: mpsc::Sender<Vec<u8>>,
+) -> anyhow::Result<()> {
+    let mut chunk = Vec::new();
+    let mut features = 0;
+    while let Some(pt) = fixes.next().await {
+        let pt = pt?;
+        let annotations = export::annotations(&pt);
+        let mut properties = Map::new();
+        for column in columns {
+            if *column == Column::Annotations {
+                // Annotations are flattened into the properties
+                for (key, value) in &annotations {
+                    properties.entry(key).or_insert_with(|| value.clone());
+                }
+            } else {
+                properties.insert(column.name().to_string(), column.value(&pt, &annotations));
+            }
+        }
+        let feature = json!({
+            "type": "Feature",
+            "geometry": {
+                "type": "Point",
+                "coordinates": [pt.x, pt.y],
+            },
+            "properties": properties,
+        });
+        chunk.push(RS);
+        serde_json::to_writer(&mut chunk, &feature)?;
+        chunk.push(b'\n');
+        features += 1;
+        if features % CHUNK_SIZE == 0 {
+            tx.send(std::mem::take(&mut chunk)).await?;
+        }
+    }
+    if !chunk.is_empty() {
+        tx.send(chunk).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::stream;
+    use serde_json::Value;
+
+    async fn records(columns: &str, points: Vec<GpsPoint>) -> Vec<u8> {
+        let selection = ExportSelection {
+            device_id: 1,
+            date_from: None,
+            date_to: None,
+            columns: Some(columns.to_string()),
+        };
+        let columns = selection.columns(DEFAULT_COLUMNS).unwrap();
+        let (tx, mut rx) = mpsc::channel(4);
+        let fixes = stream::iter(points.into_iter().map(Ok)).boxed();
+        write_fixes(&columns, fixes, tx).await.unwrap();
+        let mut seq = Vec::new();
+        while let Some(chunk) = rx.recv().await {
+            seq.extend(chunk);
+        }
+        seq
+    }
+
+    fn point(x: f64) -> GpsPoint {
+        GpsPoint {
+            x,
+            y: 47.05,
+            ts: "2025-10-09 08:00:00".to_string(),
+            tid: "me".to_string(),
+            annotations: r#"{"batt":80,"conn":"w"}"#.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn text_sequence() {
+        let seq = records("tid,annotations.batt", vec![point(9.43), point(9.44)]).await;
+        let texts: Vec<&[u8]> = seq.split_inclusive(|b| *b == b'\n').collect();
+        assert_eq!(texts.len(), 2);
+        for text in &texts {
+            assert_eq!(text.first(), Some(&RS));
+            assert_eq!(text.last(), Some(&b'\n'));
+        }
+        let feature: Value = serde_json::from_slice(&texts[1][1..]).unwrap();
+        assert_eq!(feature["geometry"]["coordinates"], json!([9.44, 47.05]));
+        assert_eq!(feature["properties"], json!({"tid": "me", "batt": 80}));
+        assert!(records("", Vec::new()).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn flattened_annotations() {
+        let seq = records("tid,annotations", vec![point(9.43)]).await;
+        let feature: Value = serde_json::from_slice(&seq[1..]).unwrap();
+        assert_eq!(
+            feature["properties"],
+            json!({"tid": "me", "batt": 80, "conn": "w"})
+        );
+    }
+}
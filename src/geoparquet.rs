@@ -0,0 +1,396 @@
+//! GeoParquet export of logged fixes.
+//!
+//! Rows are written in row groups, each sent as soon as it is complete, so exports of
+//! long histories need only memory for a single row group.
+
+use crate::db::{parse_timestamp, Db, GpsPoint};
+use crate::export::ExportSelection;
+use futures_util::stream::BoxStream;
+use futures_util::StreamExt;
+use parquet::basic::Compression;
+use parquet::data_type::{ByteArray, ByteArrayType, DoubleType, Int32Type, Int64Type};
+use parquet::file::metadata::KeyValue;
+use parquet::file::properties::WriterProperties;
+use parquet::file::writer::SerializedFileWriter;
+use parquet::schema::parser::parse_message_type;
+use serde_json::{json, Map, Value};
+use std::sync::Arc;
+use tokio::sync::mpsc;
+
+/// Number of rows per row group
+const ROW_GROUP_SIZE: usize = 50000;
+
+/// Column order has to match `RowGroup::write`
+const SCHEMA: &str = "
+message gpslog {
+    REQUIRED BYTE_ARRAY geometry;
+    REQUIRED INT64 ts (TIMESTAMP(MICROS, true));
+    REQUIRED BYTE_ARRAY tid (STRING);
+    OPTIONAL INT32 speed (INTEGER(16, true));
+    OPTIONAL INT32 elevation (INTEGER(16, true));
+    OPTIONAL INT32 accuracy;
+    OPTIONAL INT32 v_accuracy (INTEGER(16, true));
+    OPTIONAL INT32 cog (INTEGER(16, true));
+    OPTIONAL INT32 battery;
+    OPTIONAL INT32 battery_status;
+    OPTIONAL DOUBLE pressure;
+    OPTIONAL BYTE_ARRAY connection (STRING);
+    OPTIONAL BYTE_ARRAY trigger (STRING);
+    OPTIONAL BYTE_ARRAY tag (STRING);
+    OPTIONAL BYTE_ARRAY poi (STRING);
+    OPTIONAL BYTE_ARRAY ssid (STRING);
+    OPTIONAL BYTE_ARRAY bssid (STRING);
+    OPTIONAL BYTE_ARRAY annotations (JSON);
+}
+";
+
+/// Annotations stored in the integer columns `battery` and `battery_status`
+const INT_ANNOTATIONS: [&str; 2] = ["batt", "bs"];
+/// Annotation stored in the column `pressure` (kPa)
+const PRESSURE_ANNOTATION: &str = "p";
+/// Annotations stored in the string columns `connection` to `bssid`
+const STRING_ANNOTATIONS: [&str; 6] = ["conn", "t", "tag", "poi", "SSID", "BSSID"];
+
+/// Values and definition levels of an optional column
+struct OptColumn<T> {
+    values: Vec<T>,
+    def_levels: Vec<i16>,
+}
+
+impl<T> Default for OptColumn<T> {
+    fn default() -> Self {
+        OptColumn {
+            values: Vec::new(),
+            def_levels: Vec::new(),
+        }
+    }
+}
+
+impl<T> OptColumn<T> {
+    fn push(&mut self, value: Option<T>) {
+        match value {
+            Some(value) => {
+                self.values.push(value);
+                self.def_levels.push(1);
+            }
+            None => self.def_levels.push(0),
+        }
+    }
+}
+
+#[derive(Default)]
+struct RowGroup {
+    geometry: Vec<ByteArray>,
+    ts: Vec<i64>,
+    tid: Vec<ByteArray>,
+    /// speed, elevation, accuracy, v_accuracy, cog, battery, battery_status
+    ints: [OptColumn<i32>; 7],
+    pressure: OptColumn<f64>,
+    /// connection, trigger, tag, poi, ssid, bssid, annotations
+    strings: [OptColumn<ByteArray>; 7],
+}
+
+impl RowGroup {
+    fn len(&self) -> usize {
+        self.ts.len()
+    }
+
+    fn push(&mut self, pt: &GpsPoint, ts: i64) {
+        self.geometry.push(wkb_point(pt.x, pt.y).into());
+        self.ts.push(ts);
+        self.tid.push(pt.tid.as_str().into());
+        let mut annotations: Map<String, Value> =
+            serde_json::from_str(&pt.annotations).unwrap_or_default();
+        // Drop OwnTracks message metadata like `_type` and `_id`
+        annotations.retain(|key, _| !key.starts_with('_'));
+        let ints = [
+            pt.speed.map(i32::from),
+            pt.elevation.map(i32::from),
+            pt.accuracy,
+            pt.v_accuracy.map(i32::from),
+            pt.cog.map(i32::from),
+        ]
+        .into_iter()
+        .chain(INT_ANNOTATIONS.iter().map(|key| {
+            annotations
+                .remove(*key)
+                .and_then(|v| v.as_i64())
+                .and_then(|v| i32::try_from(v).ok())
+        }));
+        for (column, value) in self.ints.iter_mut().zip(ints) {
+            column.push(value);
+        }
+        self.pressure.push(
+            annotations
+                .remove(PRESSURE_ANNOTATION)
+                .and_then(|v| v.as_f64()),
+        );
+        let mut strings: Vec<Option<String>> = STRING_ANNOTATIONS
+            .iter()
+            .map(|key| match annotations.remove(*key) {
+                Some(Value::String(s)) => Some(s),
+                Some(Value::Null) | None => None,
+                Some(v) => Some(v.to_string()),
+            })
+            .collect();
+        // Remaining annotations as JSON
+        strings.push((!annotations.is_empty()).then(|| Value::Object(annotations).to_string()));
+        for (column, value) in self.strings.iter_mut().zip(strings) {
+            column.push(value.map(|s| ByteArray::from(s.into_bytes())));
+        }
+    }
+
+    fn write<W: std::io::Write + Send>(
+        self,
+        writer: &mut SerializedFileWriter<W>,
+    ) -> parquet::errors::Result<()> {
+        let mut row_group = writer.next_row_group()?;
+        let mut col_no = 0;
+        while let Some(mut column) = row_group.next_column()? {
+            match col_no {
+                0 => {
+                    column
+                        .typed::<ByteArrayType>()
+                        .write_batch(&self.geometry, None, None)?;
+                }
+                1 => {
+                    column
+                        .typed::<Int64Type>()
+                        .write_batch(&self.ts, None, None)?;
+                }
+                2 => {
+                    column
+                        .typed::<ByteArrayType>()
+                        .write_batch(&self.tid, None, None)?;
+                }
+                3..=9 => {
+                    let col = &self.ints[col_no - 3];
+                    column.typed::<Int32Type>().write_batch(
+                        &col.values,
+                        Some(&col.def_levels),
+                        None,
+                    )?;
+                }
+                10 => {
+                    column.typed::<DoubleType>().write_batch(
+                        &self.pressure.values,
+                        Some(&self.pressure.def_levels),
+                        None,
+                    )?;
+                }
+                _ => {
+                    let col = &self.strings[col_no - 11];
+                    column.typed::<ByteArrayType>().write_batch(
+                        &col.values,
+                        Some(&col.def_levels),
+                        None,
+                    )?;
+                }
+            }
+            column.close()?;
+            col_no += 1;
+        }
+        row_group.close()?;
+        Ok(())
+    }
+}
+
+/// Point geometry in WKB format (little endian)
+fn wkb_point(x: f64, y: f64) -> Vec<u8> {
+    let mut wkb = Vec::with_capacity(21);
+    wkb.push(1);
+    wkb.extend_from_slice(&1u32.to_le_bytes());
+    wkb.extend_from_slice(&x.to_le_bytes());
+    wkb.extend_from_slice(&y.to_le_bytes());
+    wkb
+}
+
+/// GeoParquet file metadata
+fn geo_metadata(bbox: Option<[f64; 4]>) -> String {
+    let mut column = json!({
+        "encoding": "WKB",
+        "geometry_types": ["Point"],
+    });
+    if let Some(bbox) = bbox {
+        column["bbox"] = json!(bbox);
+    }
+    json!({
+        "version": "1.1.0",
+        "primary_column": "geometry",
+        "columns": { "geometry": column },
+    })
+    .to_string()
+}
+
+/// Write GeoParquet file in chunks to a channel
+pub async fn write(
+    db: &Db,
+    selection: &ExportSelection,
+    tx: mpsc::Sender<Vec<u8>>,
+) -> anyhow::Result<()> {
+    let fixes = db.stream_fixes(
+        selection.device_id,
+        selection.date_from.as_deref(),
+        selection.date_to.as_deref(),
+    );
+    write_fixes(fixes, tx).await
+}
+
+/// Write fixes ordered by time as GeoParquet file in chunks to a channel
+async fn write_fixes(
+    mut fixes: BoxStream<'_, anyhow::Result<GpsPoint>>,
+    tx: mpsc::Sender<Vec<u8>>,
+) -> anyhow::Result<()> {
+    let props = WriterProperties::builder()
+        .set_compression(Compression::SNAPPY)
+        .set_created_by(format!("owntrack-rs {}", env!("CARGO_PKG_VERSION")))
+        .build();
+    let mut writer = SerializedFileWriter::new(
+        Vec::new(),
+        Arc::new(parse_message_type(SCHEMA)?),
+        Arc::new(props),
+    )?;
+    let mut bbox: Option<[f64; 4]> = None;
+    let mut rows = RowGroup::default();
+    while let Some(pt) = fixes.next().await {
+        let pt = pt?;
+        let Some(ts) = parse_timestamp(&pt.ts) else {
+            continue;
+        };
+        bbox = Some(match bbox {
+            Some([xmin, ymin, xmax, ymax]) => [
+                xmin.min(pt.x),
+                ymin.min(pt.y),
+                xmax.max(pt.x),
+                ymax.max(pt.y),
+            ],
+            None => [pt.x, pt.y, pt.x, pt.y],
+        });
+        rows.push(&pt, ts.timestamp_micros());
+        if rows.len() >= ROW_GROUP_SIZE {
+            std::mem::take(&mut rows).write(&mut writer)?;
+            writer.flush()?;
+            tx.send(std::mem::take(writer.inner_mut())).await?;
+        }
+    }
+    if rows.len() > 0 {
+        rows.write(&mut writer)?;
+    }
+    writer.append_key_value_metadata(KeyValue::new("geo".to_string(), geo_metadata(bbox)));
+    tx.send(writer.into_inner()?).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::web::Bytes;
+    use futures_util::stream;
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+    use parquet::record::RowAccessor;
+
+    fn point(x: f64, y: f64, ts: &str, annotations: &str) -> GpsPoint {
+        GpsPoint {
+            x,
+            y,
+            ts: ts.to_string(),
+            tid: "me".to_string(),
+            speed: Some(5),
+            annotations: annotations.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn round_trip() {
+        let points = vec![
+            point(
+                9.43,
+                47.05,
+                "2025-10-09 08:00:00",
+                r#"{"_id":"x","batt":80,"p":95.5}"#,
+            ),
+            point(
+                9.44,
+                47.06,
+                "2025-10-09 08:01:00",
+                r#"{"conn":"w","foo":1}"#,
+            ),
+        ];
+        let (tx, mut rx) = mpsc::channel(4);
+        let fixes = stream::iter(points.into_iter().map(Ok)).boxed();
+        write_fixes(fixes, tx).await.unwrap();
+        let mut file = Vec::new();
+        while let Some(chunk) = rx.recv().await {
+            file.extend(chunk);
+        }
+
+        let reader = SerializedFileReader::new(Bytes::from(file)).unwrap();
+        let metadata = reader.metadata().file_metadata();
+        let geo = metadata
+            .key_value_metadata()
+            .unwrap()
+            .iter()
+            .find(|kv| kv.key == "geo")
+            .and_then(|kv| kv.value.as_deref())
+            .unwrap();
+        let geo: Value = serde_json::from_str(geo).unwrap();
+        assert_eq!(geo["primary_column"], "geometry");
+        assert_eq!(geo["columns"]["geometry"]["encoding"], "WKB");
+        assert_eq!(
+            geo["columns"]["geometry"]["bbox"],
+            json!([9.43, 47.05, 9.44, 47.06])
+        );
+
+        let columns: Vec<_> = metadata
+            .schema_descr()
+            .columns()
+            .iter()
+            .map(|column| column.name().to_string())
+            .collect();
+        let schema = parse_message_type(SCHEMA).unwrap();
+        let expected: Vec<_> = schema
+            .get_fields()
+            .iter()
+            .map(|field| field.name().to_string())
+            .collect();
+        assert_eq!(columns, expected);
+
+        let rows: Vec<_> = reader
+            .get_row_iter(None)
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(rows.len(), 2);
+        let column = |name: &str| columns.iter().position(|c| c == name).unwrap();
+        let row = &rows[0];
+        assert_eq!(
+            row.get_bytes(column("geometry")).unwrap().data(),
+            wkb_point(9.43, 47.05)
+        );
+        assert_eq!(
+            row.get_timestamp_micros(column("ts")).unwrap(),
+            1759996800 * 1_000_000
+        );
+        assert_eq!(row.get_string(column("tid")).unwrap(), "me");
+        assert_eq!(row.get_short(column("speed")).unwrap(), 5);
+        assert_eq!(row.get_int(column("battery")).unwrap(), 80);
+        assert_eq!(row.get_double(column("pressure")).unwrap(), 95.5);
+        assert!(row.get_string(column("annotations")).is_err());
+        let row = &rows[1];
+        assert_eq!(row.get_string(column("connection")).unwrap(), "w");
+        assert_eq!(
+            row.get_string(column("annotations")).unwrap(),
+            r#"{"foo":1}"#
+        );
+    }
+
+    #[test]
+    fn wkb() {
+        let wkb = wkb_point(1.0, -2.0);
+        assert_eq!(wkb.len(), 21);
+        assert_eq!(&wkb[..5], [1, 1, 0, 0, 0]);
+        assert_eq!(f64::from_le_bytes(wkb[5..13].try_into().unwrap()), 1.0);
+        assert_eq!(f64::from_le_bytes(wkb[13..].try_into().unwrap()), -2.0);
+    }
+}
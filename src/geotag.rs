@@ -0,0 +1,239 @@
+use crate::db::Db;
+use crate::interpolate::{self, InterpolatedPosition};
+use chrono::{FixedOffset, Local, NaiveDate, Offset, TimeZone};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+const PHOTO_EXTENSIONS: &[&str] = &["jpg", "jpeg", "heic", "heif"];
+
+#[derive(clap::Args, Debug)]
+pub struct GeotagArgs {
+    /// Directory containing JPEG or HEIC files
+    pub dir: PathBuf,
+    /// Device whose positions are used
+    #[arg(long)]
+    pub device_id: i32,
+    /// Seconds to add to the camera clock
+    #[arg(long, default_value_t = 0, allow_hyphen_values = true)]
+    pub clock_offset: i64,
+    /// UTC offset of camera times without time zone information, e.g. `+02:00` (default: local time zone)
+    #[arg(long, allow_hyphen_values = true)]
+    pub utc_offset: Option<FixedOffset>,
+    /// Maximal time to the nearest fix in seconds
+    #[arg(long, default_value_t = 3600, value_parser = clap::value_parser!(i64).range(0..=86400))]
+    pub max_gap: i64,
+    /// Overwrite existing sidecar files
+    #[arg(long)]
+    pub overwrite: bool,
+}
+
+/// Write XMP sidecar files with GPS positions for photos in a directory
+pub async fn geotag(db: &Db, args: &GeotagArgs) -> anyhow::Result<()> {
+    let mut photos: Vec<(PathBuf, i64)> = Vec::new();
+    for entry in std::fs::read_dir(&args.dir)? {
+        let path = entry?.path();
+        let is_photo = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| PHOTO_EXTENSIONS.contains(&ext.to_lowercase().as_str()));
+        if !is_photo {
+            continue;
+        }
+        let sidecar = sidecar_path(&path);
+        if sidecar.exists() && !args.overwrite {
+            log::info!("Skipping `{}`: sidecar file exists", path.display());
+            continue;
+        }
+        match capture_time(&path, args.utc_offset) {
+            Ok(ts) => photos.push((path, ts + args.clock_offset)),
+            Err(e) => log::warn!("Skipping `{}`: {e}", path.display()),
+        }
+    }
+    if photos.is_empty() {
+        log::info!("No photos found");
+        return Ok(());
+    }
+    let mut tagged = 0;
+    for (path, ts) in &photos {
+        // Fixes around the capture time, photos may be taken months apart
+        let points = db
+            .query_fixes(
+                args.device_id,
+                ts.saturating_sub(args.max_gap),
+                ts.saturating_add(args.max_gap),
+            )
+            .await?;
+        let fixes = interpolate::timed_fixes(&points);
+        let Some(pos) = interpolate::position_at(&fixes, *ts, args.max_gap) else {
+            log::warn!("No position found for `{}`", path.display());
+            continue;
+        };
+        std::fs::write(sidecar_path(path), xmp_sidecar(&pos, *ts))?;
+        log::info!(
+            "{}: {:.6}, {:.6} (±{:.0}m)",
+            path.display(),
+            pos.y,
+            pos.x,
+            pos.uncertainty
+        );
+        tagged += 1;
+    }
+    log::info!("{tagged} of {} photos geotagged", photos.len());
+    Ok(())
+}
+
+/// Sidecar file `IMG_1.jpg.xmp`, keeping photos with the same stem apart
+fn sidecar_path(path: &Path) -> PathBuf {
+    let mut sidecar = path.as_os_str().to_owned();
+    sidecar.push(".xmp");
+    PathBuf::from(sidecar)
+}
+
+/// Capture time of a photo in seconds since epoch
+fn capture_time(path: &Path, utc_offset: Option<FixedOffset>) -> anyhow::Result<i64> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let exif = exif::Reader::new().read_from_container(&mut reader)?;
+    let ascii = |tag| match exif.get_field(tag, exif::In::PRIMARY).map(|f| &f.value) {
+        Some(exif::Value::Ascii(values)) => values.first().cloned(),
+        _ => None,
+    };
+    let datetime = ascii(exif::Tag::DateTimeOriginal)
+        .or_else(|| ascii(exif::Tag::DateTime))
+        .ok_or(anyhow::anyhow!("capture time missing"))?;
+    exif_timestamp(
+        &datetime,
+        ascii(exif::Tag::OffsetTimeOriginal).as_deref(),
+        utc_offset,
+    )
+}
+
+/// Seconds since epoch of an EXIF date time with optional EXIF offset time
+fn exif_timestamp(
+    datetime: &[u8],
+    offset_time: Option<&[u8]>,
+    utc_offset: Option<FixedOffset>,
+) -> anyhow::Result<i64> {
+    let mut dt = exif::DateTime::from_ascii(datetime)?;
+    if let Some(offset) = offset_time {
+        // Ignore invalid time zone information
+        let _ = dt.parse_offset(offset);
+    }
+    let naive = NaiveDate::from_ymd_opt(dt.year as i32, dt.month as u32, dt.day as u32)
+        .and_then(|date| date.and_hms_opt(dt.hour as u32, dt.minute as u32, dt.second as u32))
+        .ok_or(anyhow::anyhow!("invalid capture time"))?;
+    let offset = match dt.offset {
+        Some(minutes) => FixedOffset::east_opt(minutes as i32 * 60),
+        None => utc_offset.or_else(|| {
+            Local
+                .from_local_datetime(&naive)
+                .earliest()
+                .map(|dt| dt.offset().fix())
+        }),
+    }
+    .ok_or(anyhow::anyhow!("invalid time zone"))?;
+    let ts = naive
+        .and_local_timezone(offset)
+        .single()
+        .ok_or(anyhow::anyhow!("invalid capture time"))?;
+    Ok(ts.timestamp())
+}
+
+/// XMP coordinate in format `DDD,MM.mmmmmmk`
+fn xmp_coordinate(value: f64, pos_ref: char, neg_ref: char) -> String {
+    let degrees = value.abs().trunc();
+    let minutes = (value.abs() - degrees) * 60.0;
+    let direction = if value < 0.0 { neg_ref } else { pos_ref };
+    format!("{degrees},{minutes:.6}{direction}")
+}
+
+fn xmp_sidecar(pos: &InterpolatedPosition, ts: i64) -> String {
+    let time = chrono::DateTime::from_timestamp(ts, 0)
+        .map(|dt| dt.format("%FT%TZ").to_string())
+        .unwrap_or_default();
+    let altitude = pos
+        .elevation
+        .map(|elev| {
+            format!(
+                "\n    exif:GPSAltitudeRef=\"{}\"\n    exif:GPSAltitude=\"{}/10\"",
+                if elev < 0.0 { 1 } else { 0 },
+                (elev.abs() * 10.0).round()
+            )
+        })
+        .unwrap_or_default();
+    format!(
+        r#"<?xpacket begin="" id="W5M0MpCehiHzreSzNTczkc9d"?>
+<x:xmpmeta xmlns:x="adobe:ns:meta/">
+ <rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+  <rdf:Description rdf:about=""
+    xmlns:exif="http://ns.adobe.com/exif/1.0/"
+    exif:GPSVersionID="2.3.0.0"
+    exif:GPSMapDatum="WGS-84"
+    exif:GPSLatitude="{lat}"
+    exif:GPSLongitude="{lon}"{altitude}
+    exif:GPSHPositioningError="{uncertainty}/1"
+    exif:GPSTimeStamp="{time}"/>
+ </rdf:RDF>
+</x:xmpmeta>
+<?xpacket end="w"?>
+"#,
+        lat = xmp_coordinate(pos.y, 'N', 'S'),
+        lon = xmp_coordinate(pos.x, 'E', 'W'),
+        uncertainty = pos.uncertainty.round(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 2025-10-09 08:00:00 UTC
+    const TS: i64 = 1759996800;
+
+    #[test]
+    fn capture_time_offset() {
+        let utc_offset = FixedOffset::east_opt(5 * 3600);
+        let ts = exif_timestamp(b"2025:10:09 10:00:00", Some(b"+02:00"), utc_offset).unwrap();
+        assert_eq!(ts, TS);
+        // Camera time without time zone
+        let utc_offset = FixedOffset::east_opt(2 * 3600);
+        let ts = exif_timestamp(b"2025:10:09 10:00:00", None, utc_offset).unwrap();
+        assert_eq!(ts, TS);
+        let ts = exif_timestamp(b"2025:10:09 10:00:00", Some(b"invalid"), utc_offset).unwrap();
+        assert_eq!(ts, TS);
+        assert!(exif_timestamp(b"2025:13:09 10:00:00", None, utc_offset).is_err());
+    }
+
+    #[test]
+    fn sidecar() {
+        assert_eq!(
+            sidecar_path(Path::new("trip/IMG_1.jpg")),
+            PathBuf::from("trip/IMG_1.jpg.xmp")
+        );
+        assert_ne!(
+            sidecar_path(Path::new("IMG_1.jpg")),
+            sidecar_path(Path::new("IMG_1.heic"))
+        );
+        let pos = InterpolatedPosition {
+            tid: "me".to_string(),
+            x: -9.25,
+            y: 47.5,
+            elevation: Some(-12.34),
+            gap: 30,
+            uncertainty: 14.6,
+            interpolated: true,
+        };
+        let xmp = xmp_sidecar(&pos, TS);
+        assert!(xmp.contains(r#"exif:GPSLatitude="47,30.000000N""#));
+        assert!(xmp.contains(r#"exif:GPSLongitude="9,15.000000W""#));
+        assert!(xmp.contains(r#"exif:GPSAltitudeRef="1""#));
+        assert!(xmp.contains(r#"exif:GPSAltitude="123/10""#));
+        assert!(xmp.contains(r#"exif:GPSHPositioningError="15/1""#));
+        assert!(xmp.contains(r#"exif:GPSTimeStamp="2025-10-09T08:00:00Z""#));
+        let pos = InterpolatedPosition {
+            elevation: None,
+            ..pos
+        };
+        assert!(!xmp_sidecar(&pos, TS).contains("GPSAltitude"));
+    }
+}
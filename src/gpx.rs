@@ -1,64 +1,247 @@
-use crate::db::TrackData;
-use geo_types::Point;
-use gpx::{Gpx, GpxVersion, Track, TrackSegment, Waypoint};
-use time::{macros::format_description, OffsetDateTime};
+use crate::db::{GpsPoint, TrackData};
+use crate::geojson::MAX_ACCURACY;
+use crate::stationary::{self, StationaryConfig};
+use crate::stats::BboxStats;
+use chrono::{SecondsFormat, Utc};
+use serde_json::Value;
+use std::fmt::Write as _;
 
-/// Build a GPX track from track data.
-pub fn tracks(tracks: &[TrackData]) -> anyhow::Result<String> {
-    let ot_format =
-        format_description!("[year]-[month]-[day] [hour]:[minute]:[second][offset_hour]");
-    let tracks = tracks
+/// Maximal time between points of a track segment in seconds
+const MAX_SEGMENT_GAP: i64 = 600;
+/// Namespace of custom point extensions, documented in the README
+const OT_NAMESPACE: &str = concat!(env!("CARGO_PKG_REPOSITORY"), "#gpx-extensions");
+
+/// Split points into segments at time gaps
+fn segments(points: Vec<&GpsPoint>) -> Vec<Vec<&GpsPoint>> {
+    let mut segments: Vec<Vec<&GpsPoint>> = Vec::new();
+    let mut last_ts = None;
+    for point in points {
+        let ts = point.epoch();
+        let gap = match (last_ts, ts) {
+            (Some(t0), Some(t1)) => t1 - t0 > MAX_SEGMENT_GAP,
+            _ => false,
+        };
+        match segments.last_mut() {
+            Some(segment) if !gap => segment.push(point),
+            _ => segments.push(vec![point]),
+        }
+        // Collapsed stays last until the end of their duration
+        last_ts = ts
+            .map(|ts| ts + stationary::stay_duration(&point.annotations))
+            .or(last_ts);
+    }
+    segments
+}
+
+/// Detected stays of a track, using already collapsed points if present
+fn stays(points: &[GpsPoint], cfg: &StationaryConfig) -> Vec<GpsPoint> {
+    let points: Vec<GpsPoint> = points
         .iter()
-        .map(|track| {
-            let track_segment = TrackSegment {
-                points: track
-                    .points
-                    .iter()
-                    .filter_map(|point| {
-                        // keep only points within 200 meters accuracy
-                        if point.accuracy.unwrap_or(0) < 200 {
-                            let time: Option<gpx::Time> =
-                                OffsetDateTime::parse(&point.ts, &ot_format)
-                                    .map(|dt| dt.into())
-                                    .ok();
-                            let mut wpt = Waypoint::new(Point::new(point.x, point.y));
-                            wpt.time = time;
-                            wpt.elevation = point.elevation.map(|val| val as f64);
-                            wpt.speed = point.speed.map(|val| val as f64 / 3.6);
-                            Some(wpt)
-                        } else {
-                            None
-                        }
-                    })
-                    .collect(),
-            };
-            Track {
-                name: Some(format!(
-                    "Track {date}-{device_id}",
-                    date = track.date,
-                    device_id = track.device_id
-                )),
-                comment: None,
-                description: None,
-                source: None,
-                links: vec![],
-                type_: None,
-                number: None,
-                segments: vec![track_segment],
+        .filter(|point| point.accuracy.unwrap_or(0) < MAX_ACCURACY)
+        .cloned()
+        .collect();
+    let points = if points
+        .iter()
+        .any(|pt| stationary::stay_duration(&pt.annotations) > 0)
+    {
+        points
+    } else {
+        stationary::collapse(points, cfg)
+    };
+    points
+        .into_iter()
+        .filter(|pt| stationary::stay_duration(&pt.annotations) > 0)
+        .collect()
+}
+
+/// Elevation and time elements of a waypoint
+fn write_ele_time(gpx: &mut String, indent: &str, point: &GpsPoint) -> std::fmt::Result {
+    if let Some(ele) = point.elevation {
+        writeln!(gpx, "{indent}<ele>{ele}</ele>")?;
+    }
+    if let Some(time) = point.rfc3339() {
+        writeln!(gpx, "{indent}<time>{time}</time>")?;
+    }
+    Ok(())
+}
+
+fn write_trkpt(gpx: &mut String, point: &GpsPoint) -> std::fmt::Result {
+    writeln!(gpx, r#"      <trkpt lat="{}" lon="{}">"#, point.y, point.x)?;
+    write_ele_time(gpx, "        ", point)?;
+    let annotations: serde_json::Map<String, Value> =
+        serde_json::from_str(&point.annotations).unwrap_or_default();
+    let battery = annotations.get("batt").and_then(Value::as_i64);
+    let has_tpx = point.speed.is_some() || point.cog.is_some();
+    let has_ot = point.accuracy.is_some() || point.v_accuracy.is_some() || battery.is_some();
+    if has_tpx || has_ot {
+        writeln!(gpx, "        <extensions>")?;
+        if has_tpx {
+            writeln!(gpx, "          <gpxtpx:TrackPointExtension>")?;
+            if let Some(speed) = point.speed {
+                // km/h to m/s
+                let speed = speed as f64 / 3.6;
+                writeln!(gpx, "            <gpxtpx:speed>{speed:.2}</gpxtpx:speed>")?;
+            }
+            if let Some(cog) = point.cog {
+                writeln!(gpx, "            <gpxtpx:course>{cog}</gpxtpx:course>")?;
             }
+            writeln!(gpx, "          </gpxtpx:TrackPointExtension>")?;
+        }
+        if let Some(accuracy) = point.accuracy {
+            writeln!(gpx, "          <ot:accuracy>{accuracy}</ot:accuracy>")?;
+        }
+        if let Some(v_accuracy) = point.v_accuracy {
+            writeln!(gpx, "          <ot:v_accuracy>{v_accuracy}</ot:v_accuracy>")?;
+        }
+        if let Some(battery) = battery {
+            writeln!(gpx, "          <ot:battery>{battery}</ot:battery>")?;
+        }
+        writeln!(gpx, "        </extensions>")?;
+    }
+    writeln!(gpx, "      </trkpt>")
+}
+
+fn write_stay(gpx: &mut String, stay: &GpsPoint) -> std::fmt::Result {
+    let duration = stationary::stay_duration(&stay.annotations);
+    writeln!(gpx, r#"  <wpt lat="{}" lon="{}">"#, stay.y, stay.x)?;
+    write_ele_time(gpx, "    ", stay)?;
+    writeln!(gpx, "    <name>Stay</name>")?;
+    writeln!(gpx, "    <desc>Stationary for {} min</desc>", duration / 60)?;
+    writeln!(gpx, "    <type>stay</type>")?;
+    writeln!(gpx, "    <extensions>")?;
+    writeln!(gpx, "      <ot:duration>{duration}</ot:duration>")?;
+    writeln!(gpx, "    </extensions>")?;
+    writeln!(gpx, "  </wpt>")
+}
+
+/// Build a GPX 1.1 document from track data.
+///
+/// Tracks are split into segments at time gaps and stays are added as waypoints.
+pub fn tracks(tracks: &[TrackData], stationary_cfg: &StationaryConfig) -> anyhow::Result<String> {
+    let mut gpx = String::new();
+    writeln!(gpx, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        gpx,
+        r#"<gpx version="1.1" creator="owntrack-rs {version}" xmlns="http://www.topografix.com/GPX/1/1" xmlns:gpxtpx="http://www.garmin.com/xmlschemas/TrackPointExtension/v2" xmlns:ot="{OT_NAMESPACE}" xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance" xsi:schemaLocation="http://www.topografix.com/GPX/1/1 http://www.topografix.com/GPX/1/1/gpx.xsd http://www.garmin.com/xmlschemas/TrackPointExtension/v2 https://www8.garmin.com/xmlschemas/TrackPointExtensionv2.xsd">"#,
+        version = env!("CARGO_PKG_VERSION")
+    )?;
+
+    let names: Vec<String> = tracks
+        .iter()
+        .map(|track| {
+            format!(
+                "Track {date}-{device_id}",
+                date = track.date,
+                device_id = track.device_id
+            )
         })
         .collect();
-    let gpx = Gpx {
-        version: GpxVersion::Gpx10, // Speed is only included in GPX 1.0. Will be in next georust/gpx release.
-        creator: None,
-        metadata: None,
-        waypoints: vec![],
-        tracks,
-        routes: vec![],
-    };
+    // keep only points within 200 meters accuracy
+    let points: Vec<Vec<&GpsPoint>> = tracks
+        .iter()
+        .map(|track| {
+            track
+                .points
+                .iter()
+                .filter(|point| point.accuracy.unwrap_or(0) < MAX_ACCURACY)
+                .collect()
+        })
+        .collect();
+
+    writeln!(gpx, "  <metadata>")?;
+    if let [name] = names.as_slice() {
+        writeln!(gpx, "    <name>{name}</name>")?;
+    }
+    writeln!(gpx, r#"    <link href="{}">"#, env!("CARGO_PKG_REPOSITORY"))?;
+    writeln!(gpx, "      <text>owntrack-rs</text>")?;
+    writeln!(gpx, "    </link>")?;
+    writeln!(
+        gpx,
+        "    <time>{}</time>",
+        Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true)
+    )?;
+    let bbox = BboxStats::from_xy_iter(points.iter().flatten().map(|point| (point.x, point.y)));
+    if let Some(bbox) = bbox.bbox() {
+        writeln!(
+            gpx,
+            r#"    <bounds minlat="{}" minlon="{}" maxlat="{}" maxlon="{}"/>"#,
+            bbox[1], bbox[0], bbox[3], bbox[2]
+        )?;
+    }
+    writeln!(gpx, "  </metadata>")?;
+
+    for track in tracks {
+        for stay in stays(&track.points, stationary_cfg) {
+            write_stay(&mut gpx, &stay)?;
+        }
+    }
+
+    for (name, points) in names.iter().zip(points) {
+        writeln!(gpx, "  <trk>")?;
+        writeln!(gpx, "    <name>{name}</name>")?;
+        for segment in segments(points) {
+            writeln!(gpx, "    <trkseg>")?;
+            for point in segment {
+                write_trkpt(&mut gpx, point)?;
+            }
+            writeln!(gpx, "    </trkseg>")?;
+        }
+        writeln!(gpx, "  </trk>")?;
+    }
+    writeln!(gpx, "</gpx>")?;
+    Ok(gpx)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(x: f64, ts: &str, speed: i16, accuracy: i32) -> GpsPoint {
+        GpsPoint {
+            x,
+            y: 47.05,
+            ts: ts.to_string(),
+            speed: Some(speed),
+            accuracy: Some(accuracy),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn stays_ignore_inaccurate_points() {
+        let cfg = StationaryConfig {
+            radius: 50.0,
+            min_duration: 300,
+            max_speed: 2,
+        };
+        let points = vec![
+            point(9.4300, "2025-10-09 08:00:00", 20, 10),
+            point(9.4400, "2025-10-09 08:01:00", 0, 10),
+            point(9.4600, "2025-10-09 08:03:00", 0, 500),
+            point(9.4401, "2025-10-09 08:04:00", 0, 10),
+            point(9.4400, "2025-10-09 08:07:00", 0, 10),
+            point(9.4500, "2025-10-09 08:08:00", 20, 10),
+        ];
+        let stays = stays(&points, &cfg);
+        assert_eq!(stays.len(), 1);
+        assert_eq!(stationary::stay_duration(&stays[0].annotations), 360);
+    }
 
-    let mut vec = Vec::new();
-    gpx::write(&gpx, &mut vec).unwrap();
-    let gpxstr = String::from_utf8(vec).unwrap();
-    Ok(gpxstr)
+    #[test]
+    fn gpx_extensions() {
+        let track = TrackData {
+            device_id: 1,
+            date: "2025-10-09".to_string(),
+            points: vec![point(9.43, "2025-10-09 08:00:00", 36, 10)],
+        };
+        let cfg = StationaryConfig {
+            radius: 50.0,
+            min_duration: 300,
+            max_speed: 2,
+        };
+        let gpx = tracks(&[track], &cfg).unwrap();
+        assert!(gpx.contains(r#"xmlns:ot="https://github.com/pka/owntrack-rs#gpx-extensions""#));
+        assert!(gpx.contains("<gpxtpx:speed>10.00</gpxtpx:speed>"));
+        assert!(gpx.contains("<ot:accuracy>10</ot:accuracy>"));
+    }
 }
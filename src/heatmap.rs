@@ -0,0 +1,214 @@
+use crate::db::{parse_timestamp, Db};
+use crate::geojson::MAX_ACCURACY;
+use crate::projection::WebMercator;
+use crate::stationary;
+use futures_util::StreamExt;
+use serde::Deserialize;
+use std::collections::HashMap;
+
+/// Minimal cell size in meters
+const MIN_CELL_SIZE: f64 = 10.0;
+
+/// Cell shape
+#[derive(Deserialize, Clone, Copy, Default, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Grid {
+    #[default]
+    Square,
+    /// Pointy-top hexagons
+    Hex,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct HeatmapParams {
+    pub device_id: i32,
+    /// First date in format 2025-02-19
+    pub date_from: String,
+    /// Last date in format 2025-02-19 (default: `date_from`)
+    pub date_to: Option<String>,
+    pub grid: Option<Grid>,
+    /// Cell width in meters
+    pub cell_size: Option<f64>,
+    /// Maximal time attributed to a single fix in seconds
+    pub max_dwell: Option<i64>,
+}
+
+impl HeatmapParams {
+    pub fn cell_size(&self) -> f64 {
+        self.cell_size.unwrap_or(100.0).max(MIN_CELL_SIZE)
+    }
+    pub fn max_dwell(&self) -> i64 {
+        self.max_dwell.unwrap_or(600)
+    }
+}
+
+/// Aggregated grid cell
+#[derive(Debug)]
+pub struct HeatmapCell {
+    /// Closed ring of WGS84 coordinates
+    pub polygon: Vec<(f64, f64)>,
+    /// Time spent within the cell in seconds
+    pub duration: i64,
+    /// Number of fixes within the cell
+    pub count: usize,
+}
+
+/// Grid in Web Mercator coordinates
+///
+/// Cells are uniform over the whole range, with the cell size measured in ground
+/// meters at the latitude of the first fix.
+struct CellGrid {
+    grid: Grid,
+    /// Cell size in Web Mercator meters
+    size: f64,
+}
+
+impl CellGrid {
+    /// Circumradius of hexagons
+    fn hex_radius(&self) -> f64 {
+        self.size / 3f64.sqrt()
+    }
+
+    fn cell(&self, lon: f64, lat: f64) -> (i64, i64) {
+        let (x, y) = WebMercator::project(lon, lat);
+        match self.grid {
+            Grid::Square => (
+                (x / self.size).floor() as i64,
+                (y / self.size).floor() as i64,
+            ),
+            Grid::Hex => {
+                let radius = self.hex_radius();
+                let q = (3f64.sqrt() / 3.0 * x - y / 3.0) / radius;
+                let r = 2.0 / 3.0 * y / radius;
+                hex_round(q, r)
+            }
+        }
+    }
+
+    fn polygon(&self, (i, j): (i64, i64)) -> Vec<(f64, f64)> {
+        let corners: Vec<(f64, f64)> = match self.grid {
+            Grid::Square => [(0, 0), (1, 0), (1, 1), (0, 1)]
+                .iter()
+                .map(|(dx, dy)| ((i + dx) as f64 * self.size, (j + dy) as f64 * self.size))
+                .collect(),
+            Grid::Hex => {
+                let radius = self.hex_radius();
+                let cx = radius * 3f64.sqrt() * (i as f64 + j as f64 / 2.0);
+                let cy = radius * 1.5 * j as f64;
+                (0..6)
+                    .map(|k| {
+                        let angle = (60.0 * k as f64 - 30.0).to_radians();
+                        (cx + radius * angle.cos(), cy + radius * angle.sin())
+                    })
+                    .collect()
+            }
+        };
+        let mut ring: Vec<(f64, f64)> = corners
+            .into_iter()
+            .map(|(x, y)| WebMercator::unproject(x, y))
+            .collect();
+        ring.push(ring[0]);
+        ring
+    }
+}
+
+/// Round fractional axial hexagon coordinates
+fn hex_round(q: f64, r: f64) -> (i64, i64) {
+    let s = -q - r;
+    let (mut rq, mut rr, rs) = (q.round(), r.round(), s.round());
+    let (dq, dr, ds) = ((rq - q).abs(), (rr - r).abs(), (rs - s).abs());
+    if dq > dr && dq > ds {
+        rq = -rr - rs;
+    } else if dr > ds {
+        rr = -rq - rs;
+    }
+    (rq as i64, rr as i64)
+}
+
+/// Aggregate the time spent by a device into grid cells.
+///
+/// Each fix is weighted with the time until the next fix, limited to `max_dwell` seconds.
+/// Collapsed stationary points additionally count with their stay duration.
+pub async fn heatmap(db: &Db, params: &HeatmapParams) -> anyhow::Result<Vec<HeatmapCell>> {
+    let date_to = params.date_to.as_deref().unwrap_or(&params.date_from);
+    let mut fixes = db.stream_fixes(params.device_id, Some(&params.date_from), Some(date_to));
+    let mut grid: Option<CellGrid> = None;
+    let mut cells: HashMap<(i64, i64), (i64, usize)> = HashMap::new();
+    // Fixes are streamed in time order, so dwell times follow the track.
+    // Cell and end time of the previous fix
+    let mut prev: Option<((i64, i64), i64)> = None;
+    while let Some(pt) = fixes.next().await {
+        let pt = pt?;
+        if pt.accuracy.unwrap_or(0) >= MAX_ACCURACY {
+            continue;
+        }
+        let Some(ts) = parse_timestamp(&pt.ts).map(|dt| dt.timestamp()) else {
+            continue;
+        };
+        let grid = grid.get_or_insert_with(|| CellGrid {
+            grid: params.grid.unwrap_or_default(),
+            size: params.cell_size() * WebMercator::scale(pt.y),
+        });
+        let cell = grid.cell(pt.x, pt.y);
+        let stay = stationary::stay_duration(&pt.annotations);
+        if let Some((prev_cell, prev_end)) = prev {
+            let dwell = (ts - prev_end).clamp(0, params.max_dwell());
+            cells.entry(prev_cell).or_default().0 += dwell;
+        }
+        let entry = cells.entry(cell).or_default();
+        entry.0 += stay;
+        entry.1 += 1;
+        prev = Some((cell, ts + stay));
+    }
+    let Some(grid) = grid else {
+        return Ok(Vec::new());
+    };
+    let mut cells: Vec<HeatmapCell> = cells
+        .into_iter()
+        .map(|(cell, (duration, count))| HeatmapCell {
+            polygon: grid.polygon(cell),
+            duration,
+            count,
+        })
+        .collect();
+    // Draw cells with most time on top
+    cells.sort_by_key(|cell| cell.duration);
+    Ok(cells)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn square_cells_in_ground_meters() {
+        let lat = 47.05;
+        let grid = CellGrid {
+            grid: Grid::Square,
+            size: 100.0 * WebMercator::scale(lat),
+        };
+        let polygon = grid.polygon(grid.cell(9.435, lat));
+        assert_eq!(polygon.len(), 5);
+        // Width of about 100 m (1 degree longitude is about 75.9 km at this latitude)
+        let width = (polygon[1].0 - polygon[0].0) * 75_900.0;
+        assert!((width - 100.0).abs() < 1.0, "{width}");
+        // Positions 300 m apart are in different cells
+        assert_ne!(grid.cell(9.435, lat), grid.cell(9.439, lat));
+    }
+
+    #[test]
+    fn hex_cells() {
+        let grid = CellGrid {
+            grid: Grid::Hex,
+            size: 100.0 * WebMercator::scale(47.05),
+        };
+        let cell = grid.cell(9.435, 47.05);
+        let polygon = grid.polygon(cell);
+        assert_eq!(polygon.len(), 7);
+        // Center of the hexagon is within the cell
+        let (x, y) = polygon[..6].iter().fold((0.0, 0.0), |acc, pt| {
+            (acc.0 + pt.0 / 6.0, acc.1 + pt.1 / 6.0)
+        });
+        assert_eq!(grid.cell(x, y), cell);
+    }
+}
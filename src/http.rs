@@ -1,15 +1,34 @@
-use crate::db::{Db, TrackRef};
+use crate::activity::ActivityType;
+use crate::db::{day_range, Db, GpsPoint, TrackData, TrackRef};
+use crate::dem::Dem;
+use crate::encounters::{self, EncounterParams};
+use crate::explorer::{self, ExplorerParams};
+use crate::export::{self, ExportFormat, ExportSelection};
+use crate::fit;
 use crate::geojson;
 use crate::gpx;
+use crate::heatmap::{self, HeatmapParams};
+use crate::interpolate;
+use crate::kml;
+use crate::mvt::TileCoord;
 use crate::owntracks::{otrc_json, AppConfig, Message};
+use crate::profile;
+use crate::search::{self, SpatialSearchParams};
+use crate::simplify;
+use crate::smoothing;
+use crate::splits::{self, SplitParams};
+use crate::stationary::{self, StationaryConfig};
+use crate::tcx;
+use crate::tiles::{self, TileParams};
+use ::geojson::GeoJson;
 use actix_cors::Cors;
 use actix_web::{
-    error, get, middleware, middleware::Logger, post, route, web, App, HttpRequest, HttpResponse,
-    HttpServer, Responder,
+    error, get, http::header, middleware, middleware::Logger, post, route, web, App, HttpRequest,
+    HttpResponse, HttpServer, Responder,
 };
 use actix_web_rust_embed_responder::{EmbedResponse, EmbedableFileResponse, IntoResponse};
 use rust_embed_for_web::RustEmbed;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 #[derive(Deserialize)]
 struct OtParams {
@@ -59,8 +78,12 @@ async fn trackinfos(
 
 /// Get GeoJSON track
 #[get("/track")]
-async fn track(db: web::Data<Db>, track_ref: web::Query<TrackRef>) -> HttpResponse {
-    let track = match db.query_track(&track_ref).await {
+async fn track(
+    db: web::Data<Db>,
+    stationary_cfg: web::Data<StationaryConfig>,
+    track_ref: web::Query<TrackRef>,
+) -> HttpResponse {
+    let track = match query_simplified_track(&db, None, &stationary_cfg, &track_ref).await {
         Ok(data) => data,
         Err(e) => {
             log::error!("Failed to fetch track: {e}");
@@ -88,10 +111,50 @@ async fn track(db: web::Data<Db>, track_ref: web::Query<TrackRef>) -> HttpRespon
         .body(json)
 }
 
+/// Fetch track with optional DEM elevations, collapsed stationary points and smoothing
+async fn query_processed_track(
+    db: &Db,
+    dem: Option<&Dem>,
+    stationary_cfg: &StationaryConfig,
+    track_ref: &TrackRef,
+) -> anyhow::Result<TrackData> {
+    let mut track_ = db.query_track(track_ref).await?;
+    if let Some(dem) = dem {
+        dem.correct(&mut track_.points).await;
+    }
+    if track_ref.collapse.unwrap_or(false) {
+        track_.points = stationary::collapse(track_.points, stationary_cfg);
+    }
+    if let Some(method) = track_ref.smooth {
+        smoothing::smooth(&mut track_.points, method);
+    }
+    Ok(track_)
+}
+
+/// Fetch processed track with optional simplification for display and file export
+async fn query_simplified_track(
+    db: &Db,
+    dem: Option<&Dem>,
+    stationary_cfg: &StationaryConfig,
+    track_ref: &TrackRef,
+) -> anyhow::Result<TrackData> {
+    let mut track_ = query_processed_track(db, dem, stationary_cfg, track_ref).await?;
+    if let Some(method) = track_ref.simplification() {
+        track_.points =
+            simplify::simplify(track_.points, method, track_ref.tolerance, track_ref.zoom);
+    }
+    Ok(track_)
+}
+
 /// Get GPX track
 #[get("/gpxtrack")]
-async fn gpxtrack(db: web::Data<Db>, track_ref: web::Query<TrackRef>) -> HttpResponse {
-    let track_ = match db.query_track(&track_ref).await {
+async fn gpxtrack(
+    db: web::Data<Db>,
+    dem: web::Data<Dem>,
+    stationary_cfg: web::Data<StationaryConfig>,
+    track_ref: web::Query<TrackRef>,
+) -> HttpResponse {
+    let track_ = match query_simplified_track(&db, Some(&dem), &stationary_cfg, &track_ref).await {
         Ok(data) => data,
         Err(e) => {
             log::error!("Failed to fetch track: {e}");
@@ -100,7 +163,7 @@ async fn gpxtrack(db: web::Data<Db>, track_ref: web::Query<TrackRef>) -> HttpRes
                 .finish();
         }
     };
-    let gpx = match gpx::tracks(&[track_]) {
+    let gpx = match gpx::tracks(&[track_], &stationary_cfg) {
         Ok(gpx) => gpx,
         Err(e) => {
             log::error!("Failed to fetch tracks: {e}");
@@ -114,10 +177,158 @@ async fn gpxtrack(db: web::Data<Db>, track_ref: web::Query<TrackRef>) -> HttpRes
         .body(gpx)
 }
 
+/// Get KML track
+#[get("/kmltrack")]
+async fn kmltrack(
+    db: web::Data<Db>,
+    dem: web::Data<Dem>,
+    stationary_cfg: web::Data<StationaryConfig>,
+    track_ref: web::Query<TrackRef>,
+) -> HttpResponse {
+    let track_ = match query_simplified_track(&db, Some(&dem), &stationary_cfg, &track_ref).await {
+        Ok(data) => data,
+        Err(e) => {
+            log::error!("Failed to fetch track: {e}");
+            return HttpResponse::InternalServerError()
+                .reason("Failed to fetch track")
+                .finish();
+        }
+    };
+    let kml = match kml::tracks(&[track_]) {
+        Ok(kml) => kml,
+        Err(e) => {
+            log::error!("Failed to fetch tracks: {e}");
+            return HttpResponse::InternalServerError()
+                .reason("Failed to fetch track")
+                .finish();
+        }
+    };
+    HttpResponse::Ok()
+        .content_type("application/vnd.google-earth.kml+xml")
+        .body(kml)
+}
+
+/// Get KMZ track
+#[get("/kmztrack")]
+async fn kmztrack(
+    db: web::Data<Db>,
+    dem: web::Data<Dem>,
+    stationary_cfg: web::Data<StationaryConfig>,
+    track_ref: web::Query<TrackRef>,
+) -> HttpResponse {
+    let track_ = match query_simplified_track(&db, Some(&dem), &stationary_cfg, &track_ref).await {
+        Ok(data) => data,
+        Err(e) => {
+            log::error!("Failed to fetch track: {e}");
+            return HttpResponse::InternalServerError()
+                .reason("Failed to fetch track")
+                .finish();
+        }
+    };
+    let filename = format!("track-{}-{}.kmz", track_.date, track_.device_id);
+    let kmz = match kml::kmz(&[track_]) {
+        Ok(kmz) => kmz,
+        Err(e) => {
+            log::error!("Failed to fetch tracks: {e}");
+            return HttpResponse::InternalServerError()
+                .reason("Failed to fetch track")
+                .finish();
+        }
+    };
+    HttpResponse::Ok()
+        .content_type("application/vnd.google-earth.kmz")
+        .insert_header((
+            header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"{filename}\""),
+        ))
+        .body(kmz)
+}
+
+#[derive(Deserialize)]
+struct ActivityParams {
+    /// Sport of the activity
+    activity: Option<ActivityType>,
+}
+
+/// Get FIT activity file
+#[get("/fittrack")]
+async fn fittrack(
+    db: web::Data<Db>,
+    dem: web::Data<Dem>,
+    stationary_cfg: web::Data<StationaryConfig>,
+    track_ref: web::Query<TrackRef>,
+    params: web::Query<ActivityParams>,
+) -> HttpResponse {
+    let track_ = match query_simplified_track(&db, Some(&dem), &stationary_cfg, &track_ref).await {
+        Ok(data) => data,
+        Err(e) => {
+            log::error!("Failed to fetch track: {e}");
+            return HttpResponse::InternalServerError()
+                .reason("Failed to fetch track")
+                .finish();
+        }
+    };
+    let filename = format!("track-{}-{}.fit", track_.date, track_.device_id);
+    let fit = match fit::tracks(&[track_], params.activity.unwrap_or_default()) {
+        Ok(fit) => fit,
+        Err(e) => {
+            log::error!("Failed to fetch tracks: {e}");
+            return HttpResponse::InternalServerError()
+                .reason("Failed to fetch track")
+                .finish();
+        }
+    };
+    HttpResponse::Ok()
+        .content_type("application/vnd.ant.fit")
+        .insert_header((
+            header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"{filename}\""),
+        ))
+        .body(fit)
+}
+
+/// Get TCX activity
+#[get("/tcxtrack")]
+async fn tcxtrack(
+    db: web::Data<Db>,
+    dem: web::Data<Dem>,
+    stationary_cfg: web::Data<StationaryConfig>,
+    track_ref: web::Query<TrackRef>,
+    params: web::Query<ActivityParams>,
+) -> HttpResponse {
+    let track_ = match query_simplified_track(&db, Some(&dem), &stationary_cfg, &track_ref).await {
+        Ok(data) => data,
+        Err(e) => {
+            log::error!("Failed to fetch track: {e}");
+            return HttpResponse::InternalServerError()
+                .reason("Failed to fetch track")
+                .finish();
+        }
+    };
+    let tcx = match tcx::tracks(&[track_], params.activity.unwrap_or_default()) {
+        Ok(tcx) => tcx,
+        Err(e) => {
+            log::error!("Failed to fetch tracks: {e}");
+            return HttpResponse::InternalServerError()
+                .reason("Failed to fetch track")
+                .finish();
+        }
+    };
+    HttpResponse::Ok()
+        .content_type("application/vnd.garmin.tcx+xml")
+        .body(tcx)
+}
+
 /// Get GeoJSON track points
 #[get("/trackpoints")]
-async fn trackpoints(db: web::Data<Db>, track_ref: web::Query<TrackRef>) -> HttpResponse {
-    let track_ = match db.query_track(&track_ref).await {
+async fn trackpoints(
+    db: web::Data<Db>,
+    dem: web::Data<Dem>,
+    stationary_cfg: web::Data<StationaryConfig>,
+    track_ref: web::Query<TrackRef>,
+    split_params: web::Query<SplitParams>,
+) -> HttpResponse {
+    let track_ = match query_processed_track(&db, Some(&dem), &stationary_cfg, &track_ref).await {
         Ok(data) => data,
         Err(e) => {
             log::error!("Failed to fetch track: {e}");
@@ -126,7 +337,20 @@ async fn trackpoints(db: web::Data<Db>, track_ref: web::Query<TrackRef>) -> Http
                 .finish();
         }
     };
-    let geojson = geojson::track_points(&[track_]);
+    // Track length from PostGIS, as long as the stored geometry is not modified
+    let mut distance = None;
+    if !track_ref.collapse.unwrap_or(false) && track_ref.smooth.is_none() {
+        distance = match db.query_track_distance(&track_ref).await {
+            Ok(distance) => distance,
+            Err(e) => {
+                log::error!("Failed to fetch track: {e}");
+                return HttpResponse::InternalServerError()
+                    .reason("Failed to fetch track")
+                    .finish();
+            }
+        };
+    }
+    let geojson = geojson::track_points(&[track_], distance, split_params.distance());
     let json = match geojson {
         Ok(json) => json,
         Err(e) => {
@@ -141,6 +365,69 @@ async fn trackpoints(db: web::Data<Db>, track_ref: web::Query<TrackRef>) -> Http
         .body(json)
 }
 
+#[derive(Deserialize)]
+struct ProfileParams {
+    /// Number of resampled points (max. 10000)
+    n: Option<usize>,
+}
+
+/// Get elevation and speed profile
+#[get("/profile")]
+async fn track_profile(
+    db: web::Data<Db>,
+    dem: web::Data<Dem>,
+    stationary_cfg: web::Data<StationaryConfig>,
+    track_ref: web::Query<TrackRef>,
+    params: web::Query<ProfileParams>,
+) -> actix_web::Result<impl Responder> {
+    let track_ = match query_processed_track(&db, Some(&dem), &stationary_cfg, &track_ref).await {
+        Ok(data) => data,
+        Err(e) => {
+            log::error!("Failed to fetch track: {e}");
+            return Err(error::ErrorInternalServerError("Failed to fetch track"));
+        }
+    };
+    Ok(web::Json(profile::profile(&track_.points, params.n)))
+}
+
+#[derive(Serialize)]
+struct Splits {
+    splits: Vec<splits::Split>,
+    laps: Vec<splits::Split>,
+}
+
+/// Get split and lap summaries
+#[get("/splits")]
+async fn track_splits(
+    db: web::Data<Db>,
+    dem: web::Data<Dem>,
+    stationary_cfg: web::Data<StationaryConfig>,
+    track_ref: web::Query<TrackRef>,
+    params: web::Query<SplitParams>,
+) -> actix_web::Result<impl Responder> {
+    let track_ = match query_processed_track(&db, Some(&dem), &stationary_cfg, &track_ref).await {
+        Ok(data) => data,
+        Err(e) => {
+            log::error!("Failed to fetch track: {e}");
+            return Err(error::ErrorInternalServerError("Failed to fetch track"));
+        }
+    };
+    let points: Vec<&GpsPoint> = track_
+        .points
+        .iter()
+        .filter(|point| {
+            // keep only points within accuracy
+            point.accuracy.unwrap_or(0) < geojson::MAX_ACCURACY
+        })
+        .collect();
+    Ok(web::Json(Splits {
+        splits: splits::splits(&points, params.distance()),
+        laps: splits::laps(&points),
+    }))
+}
+
+const FETCH_POSITIONS_FAILED: &str = "Failed to fetch positions";
+
 /// Get GeoJSON with current device positions
 #[get("/positions")]
 async fn positions(db: web::Data<Db>, params: web::Query<TracksParams>) -> HttpResponse {
@@ -149,7 +436,7 @@ async fn positions(db: web::Data<Db>, params: web::Query<TracksParams>) -> HttpR
         Err(e) => {
             log::error!("Failed to fetch positions: {e}");
             return HttpResponse::InternalServerError()
-                .reason("Failed to fetch positions")
+                .reason(FETCH_POSITIONS_FAILED)
                 .finish();
         }
     };
@@ -158,7 +445,78 @@ async fn positions(db: web::Data<Db>, params: web::Query<TracksParams>) -> HttpR
         Err(e) => {
             log::error!("Failed to fetch positions: {e}");
             return HttpResponse::InternalServerError()
-                .reason("Failed to fetch positions")
+                .reason(FETCH_POSITIONS_FAILED)
+                .finish();
+        }
+    };
+    HttpResponse::Ok()
+        .content_type("application/geo+json")
+        .body(json)
+}
+
+/// Default maximal time to the nearest fix (seconds)
+const DEFAULT_MAX_GAP: i64 = 3600;
+const INVALID_TIME: &str = "Invalid time parameter";
+/// Maximal number of times for `/positions_at`
+const MAX_POSITION_TIMES: usize = 1000;
+/// Maximal span between the first and last time for `/positions_at` (seconds)
+const MAX_POSITION_SPAN: i64 = 7 * 24 * 3600;
+
+#[derive(Deserialize)]
+struct PositionAtParams {
+    device_id: i32,
+    /// Time in seconds since epoch or RFC 3339. Comma separated list for `/positions_at`
+    /// (max. 1000 times within 7 days).
+    ts: String,
+    /// Maximal time to the nearest fix in seconds (max. 86400)
+    max_gap: Option<i64>,
+}
+
+/// Get GeoJSON with interpolated device positions at given times
+async fn interpolated_positions(db: &Db, params: &PositionAtParams) -> HttpResponse {
+    let times: Option<Vec<i64>> = params.ts.split(',').map(interpolate::parse_time).collect();
+    let Some(times) = times.filter(|times| !times.is_empty()) else {
+        return HttpResponse::BadRequest().reason(INVALID_TIME).finish();
+    };
+    if times.len() > MAX_POSITION_TIMES {
+        return HttpResponse::BadRequest()
+            .reason("Too many time parameters")
+            .finish();
+    }
+    let (first, last) = (*times.iter().min().unwrap(), *times.iter().max().unwrap());
+    if last.saturating_sub(first) > MAX_POSITION_SPAN {
+        return HttpResponse::BadRequest()
+            .reason("Time span too large")
+            .finish();
+    }
+    let max_gap = interpolate::max_gap(params.max_gap, DEFAULT_MAX_GAP);
+    let (ts_min, ts_max) = (first.saturating_sub(max_gap), last.saturating_add(max_gap));
+    let points = match db.query_fixes(params.device_id, ts_min, ts_max).await {
+        Ok(data) => data,
+        Err(e) => {
+            log::error!("Failed to fetch positions: {e}");
+            return HttpResponse::InternalServerError()
+                .reason(FETCH_POSITIONS_FAILED)
+                .finish();
+        }
+    };
+    let fixes = interpolate::timed_fixes(&points);
+    let interpolated: Vec<_> = times
+        .into_iter()
+        .map(|ts| {
+            (
+                params.device_id,
+                ts,
+                interpolate::position_at(&fixes, ts, max_gap),
+            )
+        })
+        .collect();
+    let json = match geojson::interpolated_positions(&interpolated) {
+        Ok(json) => json,
+        Err(e) => {
+            log::error!("Failed to fetch positions: {e}");
+            return HttpResponse::InternalServerError()
+                .reason(FETCH_POSITIONS_FAILED)
                 .finish();
         }
     };
@@ -167,6 +525,347 @@ async fn positions(db: web::Data<Db>, params: web::Query<TracksParams>) -> HttpR
         .body(json)
 }
 
+/// Get GeoJSON with position of a device at a given time
+#[get("/position_at")]
+async fn position_at(db: web::Data<Db>, params: web::Query<PositionAtParams>) -> HttpResponse {
+    if params.ts.contains(',') {
+        return HttpResponse::BadRequest()
+            .reason("Use /positions_at for multiple times")
+            .finish();
+    }
+    interpolated_positions(&db, &params).await
+}
+
+/// Get GeoJSON with positions of a device at multiple times
+#[get("/positions_at")]
+async fn positions_at(db: web::Data<Db>, params: web::Query<PositionAtParams>) -> HttpResponse {
+    interpolated_positions(&db, &params).await
+}
+
+#[derive(Deserialize)]
+struct SnapshotParams {
+    /// Time in seconds since epoch or RFC 3339
+    ts: String,
+    /// Maximal time to the nearest fix in seconds (max. 86400)
+    max_gap: Option<i64>,
+}
+
+/// Get GeoJSON with interpolated positions of all devices at a given time
+#[get("/snapshot")]
+async fn snapshot(db: web::Data<Db>, params: web::Query<SnapshotParams>) -> HttpResponse {
+    let Some(ts) = interpolate::parse_time(&params.ts) else {
+        return HttpResponse::BadRequest().reason(INVALID_TIME).finish();
+    };
+    let max_gap = interpolate::max_gap(params.max_gap, DEFAULT_MAX_GAP);
+    let devices = match db
+        .query_device_fixes(ts.saturating_sub(max_gap), ts.saturating_add(max_gap))
+        .await
+    {
+        Ok(data) => data,
+        Err(e) => {
+            log::error!("Failed to fetch positions: {e}");
+            return HttpResponse::InternalServerError()
+                .reason(FETCH_POSITIONS_FAILED)
+                .finish();
+        }
+    };
+    let interpolated = interpolate::snapshot(&devices, ts, max_gap);
+    let json = match geojson::interpolated_positions(&interpolated) {
+        Ok(json) => json,
+        Err(e) => {
+            log::error!("Failed to fetch positions: {e}");
+            return HttpResponse::InternalServerError()
+                .reason(FETCH_POSITIONS_FAILED)
+                .finish();
+        }
+    };
+    HttpResponse::Ok()
+        .content_type("application/geo+json")
+        .body(json)
+}
+
+#[derive(Deserialize)]
+struct PlaybackParams {
+    date: String,
+    /// Time between frames in seconds
+    step: Option<i64>,
+    /// Maximal time to the nearest fix in seconds (max. 86400)
+    max_gap: Option<i64>,
+}
+
+/// Get interpolated positions of all devices for a day as time series of frames
+#[get("/playback")]
+async fn playback(db: web::Data<Db>, params: web::Query<PlaybackParams>) -> HttpResponse {
+    let Some((day_start, day_end)) = day_range(&params.date) else {
+        return HttpResponse::BadRequest()
+            .reason("Invalid date parameter")
+            .finish();
+    };
+    let step = interpolate::playback_step(params.step);
+    let max_gap = interpolate::max_gap(params.max_gap, DEFAULT_MAX_GAP);
+    // Include fixes around midnight for interpolation
+    let devices = match db
+        .query_device_fixes(day_start - max_gap, day_end + max_gap)
+        .await
+    {
+        Ok(data) => data,
+        Err(e) => {
+            log::error!("Failed to fetch positions: {e}");
+            return HttpResponse::InternalServerError()
+                .reason(FETCH_POSITIONS_FAILED)
+                .finish();
+        }
+    };
+    let frames = interpolate::playback_frames(&devices, (day_start, day_end), step, max_gap);
+    let json = match geojson::playback(&frames) {
+        Ok(json) => json,
+        Err(e) => {
+            log::error!("Failed to fetch positions: {e}");
+            return HttpResponse::InternalServerError()
+                .reason(FETCH_POSITIONS_FAILED)
+                .finish();
+        }
+    };
+    HttpResponse::Ok()
+        .content_type("application/json")
+        .body(json)
+}
+
+/// Get GeoJSON with encounters between devices on a given day
+#[get("/encounters")]
+async fn device_encounters(db: web::Data<Db>, params: web::Query<EncounterParams>) -> HttpResponse {
+    let Some((day_start, day_end)) = day_range(&params.date) else {
+        return HttpResponse::BadRequest()
+            .reason("Invalid date parameter")
+            .finish();
+    };
+    let (devices, track_infos) = match tokio::try_join!(
+        db.query_device_fixes(day_start, day_end),
+        db.query_tracks_info(&params.date)
+    ) {
+        Ok(data) => data,
+        Err(e) => {
+            log::error!("Failed to fetch positions: {e}");
+            return HttpResponse::InternalServerError()
+                .reason(FETCH_POSITIONS_FAILED)
+                .finish();
+        }
+    };
+    let encounters = encounters::encounters(&devices, &params);
+    let json = match geojson::encounters(&encounters, &track_infos) {
+        Ok(json) => json,
+        Err(e) => {
+            log::error!("Failed to fetch encounters: {e}");
+            return HttpResponse::InternalServerError()
+                .reason("Failed to fetch encounters")
+                .finish();
+        }
+    };
+    HttpResponse::Ok()
+        .content_type("application/geo+json")
+        .body(json)
+}
+
+/// Get tracks passing through a bounding box
+#[get("/search/spatial")]
+async fn spatial_search(
+    db: web::Data<Db>,
+    params: web::Query<SpatialSearchParams>,
+) -> actix_web::Result<impl Responder> {
+    if params.bbox.is_none() {
+        return Err(error::ErrorBadRequest("bbox parameter required"));
+    }
+    match search::spatial_search(&db, &params, None).await {
+        Ok(passages) => Ok(web::Json(passages)),
+        Err(e) => {
+            log::error!("{e}");
+            Err(error::ErrorInternalServerError("Failed to search tracks"))
+        }
+    }
+}
+
+/// Get tracks passing through a GeoJSON (Multi)Polygon area
+#[post("/search/spatial")]
+async fn spatial_search_area(
+    db: web::Data<Db>,
+    params: web::Query<SpatialSearchParams>,
+    body: String,
+) -> actix_web::Result<impl Responder> {
+    let area = body
+        .parse::<GeoJson>()
+        .map_err(|e| e.to_string())
+        .and_then(|geojson| geo::Geometry::try_from(geojson).map_err(|e| e.to_string()));
+    let area = match area {
+        Ok(area @ (geo::Geometry::Polygon(_) | geo::Geometry::MultiPolygon(_))) => area,
+        Ok(_) => return Err(error::ErrorBadRequest("Polygon or MultiPolygon expected")),
+        Err(e) => {
+            log::info!("Invalid search area: {e}");
+            return Err(error::ErrorBadRequest("Invalid GeoJSON area"));
+        }
+    };
+    match search::spatial_search(&db, &params, Some(&area)).await {
+        Ok(passages) => Ok(web::Json(passages)),
+        Err(e) => {
+            log::error!("{e}");
+            Err(error::ErrorInternalServerError("Failed to search tracks"))
+        }
+    }
+}
+
+/// Get GeoJSON grid with time spent by a device
+#[get("/heatmap")]
+async fn device_heatmap(db: web::Data<Db>, params: web::Query<HeatmapParams>) -> HttpResponse {
+    let cells = match heatmap::heatmap(&db, &params).await {
+        Ok(cells) => cells,
+        Err(e) => {
+            log::error!("Failed to compute heatmap: {e}");
+            return HttpResponse::InternalServerError()
+                .reason("Failed to compute heatmap")
+                .finish();
+        }
+    };
+    match geojson::heatmap(&cells) {
+        Ok(json) => HttpResponse::Ok()
+            .content_type("application/geo+json")
+            .body(json),
+        Err(e) => {
+            log::error!("Failed to serialize heatmap: {e}");
+            HttpResponse::InternalServerError()
+                .reason("Failed to compute heatmap")
+                .finish()
+        }
+    }
+}
+
+/// Get GeoJSON with map tiles visited by a device
+#[get("/explorer")]
+async fn explorer_tiles(db: web::Data<Db>, params: web::Query<ExplorerParams>) -> HttpResponse {
+    let tiles = match explorer::explorer_tiles(&db, &params).await {
+        Ok(tiles) => tiles,
+        Err(e) => {
+            log::error!("Failed to compute explorer tiles: {e}");
+            return HttpResponse::InternalServerError()
+                .reason("Failed to compute explorer tiles")
+                .finish();
+        }
+    };
+    match geojson::explorer(&tiles) {
+        Ok(json) => HttpResponse::Ok()
+            .content_type("application/geo+json")
+            .body(json),
+        Err(e) => {
+            log::error!("Failed to serialize explorer tiles: {e}");
+            HttpResponse::InternalServerError()
+                .reason("Failed to compute explorer tiles")
+                .finish()
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct VisitedParams {
+    device_id: Option<i32>,
+    /// First date in format 2025-02-19
+    date_from: Option<String>,
+    /// Last date in format 2025-02-19
+    date_to: Option<String>,
+}
+
+/// Get visited regions with first and last visit
+#[get("/visited")]
+async fn visited_regions(
+    db: web::Data<Db>,
+    params: web::Query<VisitedParams>,
+) -> actix_web::Result<impl Responder> {
+    match db
+        .query_region_visits(
+            params.device_id,
+            params.date_from.as_deref(),
+            params.date_to.as_deref(),
+        )
+        .await
+    {
+        Ok(visits) => Ok(web::Json(visits)),
+        Err(e) => {
+            log::error!("{e}");
+            Err(error::ErrorInternalServerError(
+                "Failed to fetch visited regions",
+            ))
+        }
+    }
+}
+
+/// Get vector tile with tracks of a date range
+#[get("/tiles/{z}/{x}/{y}.mvt")]
+async fn track_tile(
+    db: web::Data<Db>,
+    path: web::Path<(u8, u32, u32)>,
+    params: web::Query<TileParams>,
+) -> HttpResponse {
+    let (z, x, y) = path.into_inner();
+    let tile = TileCoord { z, x, y };
+    if !tile.is_valid() {
+        return HttpResponse::BadRequest().reason("Invalid tile").finish();
+    }
+    if let Err(e) = params.validate() {
+        return HttpResponse::BadRequest()
+            .reason("Invalid date range")
+            .body(e.to_string());
+    }
+    // Tiles of the current day change with new fixes
+    let max_age = if params.is_past() { 86400 } else { 60 };
+    match tiles::track_tile(&db, tile, &params).await {
+        Ok(mvt) => HttpResponse::Ok()
+            .content_type("application/vnd.mapbox-vector-tile")
+            .insert_header((header::CACHE_CONTROL, format!("private, max-age={max_age}")))
+            .body(mvt),
+        Err(e) => {
+            log::error!("Failed to render tile: {e}");
+            HttpResponse::InternalServerError()
+                .reason("Failed to render tile")
+                .finish()
+        }
+    }
+}
+
+/// Stream exported fixes of a device as file download
+fn export_response(db: &Db, selection: ExportSelection, format: ExportFormat) -> HttpResponse {
+    if let Err(e) = selection.validate() {
+        return HttpResponse::BadRequest()
+            .reason("Invalid export parameters")
+            .body(e.to_string());
+    }
+    let filename = format!("owntracks-{}.{}", selection.device_id, format.extension());
+    HttpResponse::Ok()
+        .content_type(format.content_type())
+        .insert_header((
+            header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"{filename}\""),
+        ))
+        .streaming(export::export_stream(db.clone(), selection, format))
+}
+
+/// Export fixes of a device as GeoParquet file
+#[get("/export/parquet")]
+async fn export_parquet(db: web::Data<Db>, selection: web::Query<ExportSelection>) -> HttpResponse {
+    export_response(&db, selection.into_inner(), ExportFormat::Parquet)
+}
+
+/// Export fixes of a device as CSV file
+#[get("/export/csv")]
+async fn export_csv(db: web::Data<Db>, selection: web::Query<ExportSelection>) -> HttpResponse {
+    export_response(&db, selection.into_inner(), ExportFormat::Csv)
+}
+
+/// Export fixes of a device as GeoJSON text sequence
+#[get("/export/geojsonseq")]
+async fn export_geojsonseq(
+    db: web::Data<Db>,
+    selection: web::Query<ExportSelection>,
+) -> HttpResponse {
+    export_response(&db, selection.into_inner(), ExportFormat::GeoJsonSeq)
+}
+
 #[get("/otrc")]
 async fn otrc(db: web::Data<Db>, req: HttpRequest) -> actix_web::Result<impl Responder> {
     match db.is_valid_invite().await {
@@ -198,6 +897,8 @@ async fn serve_assets(path: web::Path<String>) -> EmbedResponse<EmbedableFileRes
 
 pub async fn webserver(db: Db) -> std::io::Result<()> {
     let bind_addr = dotenvy::var("HTTP_LISTEN").unwrap_or("0.0.0.0:8083".to_string());
+    let dem = web::Data::new(Dem::from_env());
+    let stationary_cfg = web::Data::new(StationaryConfig::from_env());
     log::info!("Listening on http://{bind_addr}/");
     HttpServer::new(move || {
         let cors = if cfg!(debug_assertions) {
@@ -228,12 +929,34 @@ pub async fn webserver(db: Db) -> std::io::Result<()> {
             .wrap(middleware::Compress::default())
             .wrap(cors)
             .app_data(web::Data::new(db.clone()))
+            .app_data(dem.clone())
+            .app_data(stationary_cfg.clone())
             .service(owntracks)
             .service(trackinfos)
             .service(gpxtrack)
+            .service(kmltrack)
+            .service(kmztrack)
+            .service(fittrack)
+            .service(tcxtrack)
             .service(track)
             .service(trackpoints)
+            .service(track_profile)
+            .service(track_splits)
             .service(positions)
+            .service(position_at)
+            .service(positions_at)
+            .service(snapshot)
+            .service(playback)
+            .service(device_encounters)
+            .service(spatial_search)
+            .service(spatial_search_area)
+            .service(device_heatmap)
+            .service(explorer_tiles)
+            .service(visited_regions)
+            .service(track_tile)
+            .service(export_parquet)
+            .service(export_csv)
+            .service(export_geojsonseq)
             .service(otrc)
             .service(serve_assets)
     })
@@ -0,0 +1,296 @@
+use crate::db::{parse_timestamp, GpsPoint};
+use crate::geojson::MAX_ACCURACY;
+use chrono::DateTime;
+use geo::{Distance, Haversine, Point};
+
+/// Accuracy assumed for fixes without accuracy (meters)
+const DEFAULT_ACCURACY: f64 = 10.0;
+/// Minimal assumed travel speed between fixes (m/s)
+const MIN_TRAVEL_SPEED: f64 = 1.5;
+/// Factor applied to the observed speed between fixes for the assumed maximal speed
+const TRAVEL_SPEED_FACTOR: f64 = 1.5;
+
+/// Upper limit of the maximal time to the nearest fix (seconds)
+pub const MAX_GAP_LIMIT: i64 = 24 * 3600;
+/// Default time between playback frames (seconds)
+const DEFAULT_PLAYBACK_STEP: i64 = 60;
+/// Minimal time between playback frames (seconds)
+const MIN_PLAYBACK_STEP: i64 = 10;
+
+/// Device ID, time and interpolated position of a device
+pub type DevicePosition = (i32, i64, Option<InterpolatedPosition>);
+
+/// Position at a point in time
+#[derive(Debug)]
+pub struct InterpolatedPosition {
+    /// Tracker ID of the nearest fix
+    pub tid: String,
+    pub x: f64,
+    pub y: f64,
+    pub elevation: Option<f64>,
+    /// Time to the nearest fix (seconds)
+    pub gap: i64,
+    /// Estimated position uncertainty (meters)
+    pub uncertainty: f64,
+    /// Position between two fixes, otherwise the nearest fix is used
+    pub interpolated: bool,
+}
+
+/// Maximal time to the nearest fix within 0..=MAX_GAP_LIMIT
+pub fn max_gap(max_gap: Option<i64>, default: i64) -> i64 {
+    max_gap.unwrap_or(default).clamp(0, MAX_GAP_LIMIT)
+}
+
+/// Time between playback frames, at least MIN_PLAYBACK_STEP
+pub fn playback_step(step: Option<i64>) -> i64 {
+    step.unwrap_or(DEFAULT_PLAYBACK_STEP).max(MIN_PLAYBACK_STEP)
+}
+
+/// Parse time parameter in seconds since epoch, RFC 3339 or `2025-02-19 06:46:54+00`
+pub fn parse_time(ts: &str) -> Option<i64> {
+    let ts = ts.trim();
+    ts.parse::<i64>()
+        .ok()
+        // Reject epochs outside of the supported date range
+        .filter(|ts| DateTime::from_timestamp(*ts, 0).is_some())
+        .or_else(|| {
+            DateTime::parse_from_rfc3339(ts)
+                .ok()
+                .map(|dt| dt.timestamp())
+        })
+        .or_else(|| parse_timestamp(ts).map(|dt| dt.timestamp()))
+}
+
+/// Fixes with timestamp, filtered by accuracy and ordered by time
+pub fn timed_fixes(points: &[GpsPoint]) -> Vec<(i64, &GpsPoint)> {
+    let mut fixes: Vec<(i64, &GpsPoint)> = points
+        .iter()
+        .filter(|point| point.accuracy.unwrap_or(0) < MAX_ACCURACY)
+        .filter_map(|pt| pt.epoch().map(|ts| (ts, pt)))
+        .collect();
+    fixes.sort_by_key(|(ts, _)| *ts);
+    fixes
+}
+
+fn accuracy(pt: &GpsPoint) -> f64 {
+    pt.accuracy
+        .filter(|acc| *acc > 0)
+        .map(f64::from)
+        .unwrap_or(DEFAULT_ACCURACY)
+}
+
+/// Interpolate position at `ts` between surrounding fixes.
+///
+/// Returns `None` if the nearest fix is more than `max_gap` seconds away.
+pub fn position_at(
+    fixes: &[(i64, &GpsPoint)],
+    ts: i64,
+    max_gap: i64,
+) -> Option<InterpolatedPosition> {
+    let idx = fixes.partition_point(|(t, _)| *t < ts);
+    let before = idx.checked_sub(1).map(|i| fixes[i]);
+    let after = fixes.get(idx).copied();
+    match (before, after) {
+        (_, Some((t1, pt))) if t1 == ts => Some(InterpolatedPosition {
+            tid: pt.tid.clone(),
+            x: pt.x,
+            y: pt.y,
+            elevation: pt.elevation.map(f64::from),
+            gap: 0,
+            uncertainty: accuracy(pt),
+            interpolated: false,
+        }),
+        (Some((t0, a)), Some((t1, b))) if (ts - t0).min(t1 - ts) <= max_gap => {
+            let dt = (t1 - t0) as f64;
+            let frac = (ts - t0) as f64 / dt;
+            let (pa, pb) = (Point::new(a.x, a.y), Point::new(b.x, b.y));
+            let distance = Haversine::distance(pa, pb);
+            // Region reachable from both fixes with maximal travel speed
+            let reported_speed = a.speed.max(b.speed).map(|v| v as f64 / 3.6).unwrap_or(0.0);
+            let speed = (distance / dt * TRAVEL_SPEED_FACTOR)
+                .max(reported_speed * TRAVEL_SPEED_FACTOR)
+                .max(MIN_TRAVEL_SPEED);
+            let (r0, r1) = (speed * (ts - t0) as f64, speed * (t1 - ts) as f64);
+            let lateral = if distance > 0.0 {
+                let along = (r0 * r0 - r1 * r1 + distance * distance) / (2.0 * distance);
+                (r0 * r0 - along * along).max(0.0).sqrt()
+            } else {
+                r0.min(r1)
+            };
+            let elevation = match (a.elevation, b.elevation) {
+                (Some(e0), Some(e1)) => Some(e0 as f64 + (e1 - e0) as f64 * frac),
+                (e0, e1) => e0.or(e1).map(f64::from),
+            };
+            let nearest = if frac < 0.5 { a } else { b };
+            Some(InterpolatedPosition {
+                tid: nearest.tid.clone(),
+                x: a.x + (b.x - a.x) * frac,
+                y: a.y + (b.y - a.y) * frac,
+                elevation,
+                gap: (ts - t0).min(t1 - ts),
+                uncertainty: accuracy(a) + (accuracy(b) - accuracy(a)) * frac + lateral,
+                interpolated: true,
+            })
+        }
+        (before, after) => {
+            // Nearest fix
+            let (t, pt) = [before, after]
+                .into_iter()
+                .flatten()
+                .min_by_key(|(t, _)| (ts - t).abs())?;
+            let gap = (ts - t).abs();
+            (gap <= max_gap).then(|| InterpolatedPosition {
+                tid: pt.tid.clone(),
+                x: pt.x,
+                y: pt.y,
+                elevation: pt.elevation.map(f64::from),
+                gap,
+                uncertainty: accuracy(pt) + gap as f64 * MIN_TRAVEL_SPEED,
+                interpolated: false,
+            })
+        }
+    }
+}
+
+/// Positions of all devices at a given time
+pub fn snapshot(devices: &[(i32, Vec<GpsPoint>)], ts: i64, max_gap: i64) -> Vec<DevicePosition> {
+    devices
+        .iter()
+        .map(|(device_id, points)| {
+            let fixes = timed_fixes(points);
+            (*device_id, ts, position_at(&fixes, ts, max_gap))
+        })
+        .collect()
+}
+
+/// Positions of all devices every `step` seconds from `start` until before `end`
+pub fn playback_frames(
+    devices: &[(i32, Vec<GpsPoint>)],
+    (start, end): (i64, i64),
+    step: i64,
+    max_gap: i64,
+) -> Vec<(i64, Vec<DevicePosition>)> {
+    let device_fixes: Vec<_> = devices
+        .iter()
+        .map(|(device_id, points)| (*device_id, timed_fixes(points)))
+        .collect();
+    (start..end)
+        .step_by(step.max(1) as usize)
+        .map(|ts| {
+            let frame = device_fixes
+                .iter()
+                .map(|(device_id, fixes)| (*device_id, ts, position_at(fixes, ts, max_gap)))
+                .collect();
+            (ts, frame)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fix(x: f64, ts: &str) -> GpsPoint {
+        GpsPoint {
+            x,
+            ts: ts.to_string(),
+            accuracy: Some(10),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn time_formats() {
+        assert_eq!(parse_time("1760000000"), Some(1760000000));
+        assert_eq!(parse_time("2025-10-09T08:53:20Z"), Some(1760000000));
+        assert_eq!(parse_time("2025-10-09T10:53:20+02:00"), Some(1760000000));
+        assert_eq!(parse_time("2025-10-09 08:53:20"), Some(1760000000));
+        assert_eq!(parse_time(&i64::MAX.to_string()), None);
+        assert_eq!(parse_time("yesterday"), None);
+    }
+
+    #[test]
+    fn interpolate_between_fixes() {
+        let points = [
+            fix(0.02, "2025-10-09 08:20:00"),
+            fix(0.0, "2025-10-09 08:00:00"),
+        ];
+        let fixes = timed_fixes(&points);
+        assert_eq!(fixes[0].0, parse_time("2025-10-09 08:00:00").unwrap());
+        let t0 = fixes[0].0;
+        let pos = position_at(&fixes, t0 + 300, 600).unwrap();
+        assert!(pos.interpolated);
+        assert!((pos.x - 0.005).abs() < 1e-9);
+        assert_eq!(pos.gap, 300);
+        assert!(pos.uncertainty > 10.0);
+        let pos = position_at(&fixes, t0, 600).unwrap();
+        assert!(!pos.interpolated);
+        assert_eq!(pos.uncertainty, 10.0);
+    }
+
+    #[test]
+    fn max_gap_boundary() {
+        let points = [
+            fix(0.0, "2025-10-09 08:00:00"),
+            fix(0.02, "2025-10-09 08:20:00"),
+        ];
+        let fixes = timed_fixes(&points);
+        let (t0, t1) = (fixes[0].0, fixes[1].0);
+        // Between fixes, nearest fix at max_gap
+        assert!(position_at(&fixes, t0 + 600, 600).is_some());
+        assert!(position_at(&fixes, t0 + 600, 599).is_none());
+        // Before first and after last fix
+        let pos = position_at(&fixes, t0 - 120, 120).unwrap();
+        assert_eq!((pos.x, pos.gap, pos.interpolated), (0.0, 120, false));
+        assert!(position_at(&fixes, t0 - 121, 120).is_none());
+        assert!(position_at(&fixes, t1 + 120, 120).is_some());
+        assert!(position_at(&fixes, t1 + 121, 120).is_none());
+        assert!(position_at(&[], t0, 120).is_none());
+    }
+
+    #[test]
+    fn playback_step_clamp() {
+        assert_eq!(playback_step(None), 60);
+        assert_eq!(playback_step(Some(300)), 300);
+        assert_eq!(playback_step(Some(10)), 10);
+        assert_eq!(playback_step(Some(9)), 10);
+        assert_eq!(playback_step(Some(-60)), 10);
+    }
+
+    #[test]
+    fn frames() {
+        let t0 = parse_time("2025-10-09 08:00:00").unwrap();
+        let devices = vec![
+            (
+                1,
+                vec![
+                    fix(0.0, "2025-10-09 08:00:00"),
+                    fix(0.02, "2025-10-09 08:20:00"),
+                ],
+            ),
+            (2, vec![fix(1.0, "2025-10-09 08:25:00")]),
+        ];
+        let frames = playback_frames(&devices, (t0, t0 + 1800), 600, 600);
+        let times: Vec<_> = frames.iter().map(|(ts, _)| *ts - t0).collect();
+        assert_eq!(times, [0, 600, 1200]);
+        for (ts, frame) in &frames {
+            let ids: Vec<_> = frame.iter().map(|(id, t, _)| (*id, *t)).collect();
+            assert_eq!(ids, [(1, *ts), (2, *ts)]);
+        }
+        // Device 1 interpolated between fixes, device 2 without position until 08:20
+        let (_, _, pos) = &frames[1].1[0];
+        assert!(pos.as_ref().unwrap().interpolated);
+        assert!(frames[0].1[1].2.is_none());
+        assert!(frames[1].1[1].2.is_none());
+        let pos = frames[2].1[1].2.as_ref().unwrap();
+        assert_eq!((pos.x, pos.gap), (1.0, 300));
+        // Same positions as a snapshot at the frame time
+        let positions = snapshot(&devices, t0 + 1200, 600);
+        assert_eq!(positions.len(), 2);
+        assert_eq!(positions[0].2.as_ref().unwrap().x, 0.02);
+        assert_eq!(positions[1].2.as_ref().unwrap().x, 1.0);
+        assert!(snapshot(&devices, t0 + 3600, 600)
+            .iter()
+            .all(|(_, _, pos)| pos.is_none()));
+    }
+}
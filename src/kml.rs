@@ -0,0 +1,181 @@
+use crate::db::{GpsPoint, TrackData};
+use crate::geojson::MAX_ACCURACY;
+use std::fmt::Write as _;
+use std::io::Write as _;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+/// Track colors in format RRGGBB, selected by device id
+const DEVICE_COLORS: &[&str] = &[
+    "e41a1c", "377eb8", "4daf4a", "984ea3", "ff7f00", "a65628", "f781bf", "999999",
+];
+
+/// KML color in format AABBGGRR
+fn device_color(device_id: i32) -> String {
+    let rgb = DEVICE_COLORS[device_id.unsigned_abs() as usize % DEVICE_COLORS.len()];
+    format!("ff{}{}{}", &rgb[4..6], &rgb[2..4], &rgb[0..2])
+}
+
+fn placemark(kml: &mut String, name: &str, style: &str, point: &GpsPoint) -> std::fmt::Result {
+    writeln!(kml, "    <Placemark>")?;
+    writeln!(kml, "      <name>{name}</name>")?;
+    if let Some(when) = point.rfc3339() {
+        writeln!(kml, "      <TimeStamp><when>{when}</when></TimeStamp>")?;
+    }
+    writeln!(kml, "      <styleUrl>#{style}</styleUrl>")?;
+    writeln!(
+        kml,
+        "      <Point><coordinates>{},{}</coordinates></Point>",
+        point.x, point.y
+    )?;
+    writeln!(kml, "    </Placemark>")
+}
+
+/// Build a KML document with a `gx:Track` and start/end placemarks per track.
+pub fn tracks(tracks: &[TrackData]) -> anyhow::Result<String> {
+    let mut kml = String::new();
+    writeln!(kml, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        kml,
+        r#"<kml xmlns="http://www.opengis.net/kml/2.2" xmlns:gx="http://www.google.com/kml/ext/2.2">"#
+    )?;
+    writeln!(kml, "<Document>")?;
+    let mut device_ids: Vec<i32> = tracks.iter().map(|track| track.device_id).collect();
+    device_ids.sort();
+    device_ids.dedup();
+    for device_id in device_ids {
+        let color = device_color(device_id);
+        writeln!(kml, r#"  <Style id="device-{device_id}">"#)?;
+        writeln!(
+            kml,
+            "    <LineStyle><color>{color}</color><width>4</width></LineStyle>"
+        )?;
+        writeln!(kml, "    <IconStyle><color>{color}</color><Icon><href>http://earth.google.com/images/kml-icons/track-directional/track-0.png</href></Icon></IconStyle>")?;
+        writeln!(kml, "  </Style>")?;
+    }
+    writeln!(
+        kml,
+        r#"  <Style id="start"><IconStyle><Icon><href>http://maps.google.com/mapfiles/kml/paddle/grn-circle.png</href></Icon></IconStyle></Style>"#
+    )?;
+    writeln!(
+        kml,
+        r#"  <Style id="end"><IconStyle><Icon><href>http://maps.google.com/mapfiles/kml/paddle/red-circle.png</href></Icon></IconStyle></Style>"#
+    )?;
+    for track in tracks {
+        let name = format!(
+            "Track {date}-{device_id}",
+            date = track.date,
+            device_id = track.device_id
+        );
+        // keep only points within 200 meters accuracy
+        let points: Vec<(&GpsPoint, String)> = track
+            .points
+            .iter()
+            .filter(|point| point.accuracy.unwrap_or(0) < MAX_ACCURACY)
+            .filter_map(|point| Some((point, point.rfc3339()?)))
+            .collect();
+        writeln!(kml, "  <Folder>")?;
+        writeln!(kml, "    <name>{name}</name>")?;
+        writeln!(kml, "    <Placemark>")?;
+        writeln!(kml, "      <name>{name}</name>")?;
+        writeln!(
+            kml,
+            "      <styleUrl>#device-{}</styleUrl>",
+            track.device_id
+        )?;
+        writeln!(kml, "      <gx:Track>")?;
+        for (_, when) in &points {
+            writeln!(kml, "        <when>{when}</when>")?;
+        }
+        for (point, _) in &points {
+            writeln!(
+                kml,
+                "        <gx:coord>{} {} {}</gx:coord>",
+                point.x,
+                point.y,
+                point.elevation.unwrap_or(0)
+            )?;
+        }
+        writeln!(kml, "      </gx:Track>")?;
+        writeln!(kml, "    </Placemark>")?;
+        if let (Some((first, _)), Some((last, _))) = (points.first(), points.last()) {
+            placemark(&mut kml, "Start", "start", first)?;
+            placemark(&mut kml, "End", "end", last)?;
+        }
+        writeln!(kml, "  </Folder>")?;
+    }
+    writeln!(kml, "</Document>")?;
+    writeln!(kml, "</kml>")?;
+    Ok(kml)
+}
+
+/// Build a KMZ archive containing the KML document of the tracks.
+pub fn kmz(tracks: &[TrackData]) -> anyhow::Result<Vec<u8>> {
+    let kml = self::tracks(tracks)?;
+    let mut zip = ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    zip.start_file(
+        "doc.kml",
+        SimpleFileOptions::default().compression_method(CompressionMethod::Deflated),
+    )?;
+    zip.write_all(kml.as_bytes())?;
+    Ok(zip.finish()?.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read as _;
+
+    fn track() -> TrackData {
+        let point = |x: f64, ts: &str, accuracy: Option<i32>| GpsPoint {
+            x,
+            y: 47.05,
+            ts: ts.to_string(),
+            accuracy,
+            elevation: Some(500),
+            ..Default::default()
+        };
+        TrackData {
+            device_id: 1,
+            date: "2025-10-09".to_string(),
+            points: vec![
+                point(9.43, "2025-10-09 08:00:00", Some(10)),
+                point(9.44, "2025-10-09 08:01:00", Some(MAX_ACCURACY)),
+                point(9.45, "invalid", None),
+                point(9.46, "2025-10-09 08:03:00", None),
+            ],
+        }
+    }
+
+    #[test]
+    fn track_coordinates() {
+        let kml = tracks(&[track()]).unwrap();
+        let track = &kml[kml.find("<gx:Track>").unwrap()..kml.find("</gx:Track>").unwrap()];
+        assert_eq!(track.matches("<when>").count(), 2);
+        assert_eq!(track.matches("<gx:coord>").count(), 2);
+        assert!(track.rfind("<when>") < track.find("<gx:coord>"));
+        assert!(track.contains("<gx:coord>9.46 47.05 500</gx:coord>"));
+        assert!(kml.contains(r#"<Style id="device-1">"#));
+    }
+
+    #[test]
+    fn kml_colors() {
+        assert_eq!(device_color(0), "ff1c1ae4");
+        assert_eq!(device_color(1), "ffb87e37");
+        assert_eq!(device_color(-8), device_color(0));
+    }
+
+    #[test]
+    fn kmz_archive() {
+        let kmz = kmz(&[track()]).unwrap();
+        let mut archive = zip::ZipArchive::new(std::io::Cursor::new(kmz)).unwrap();
+        assert_eq!(archive.len(), 1);
+        let mut doc = String::new();
+        archive
+            .by_name("doc.kml")
+            .unwrap()
+            .read_to_string(&mut doc)
+            .unwrap();
+        assert_eq!(doc, tracks(&[track()]).unwrap());
+    }
+}
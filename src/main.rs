@@ -1,14 +1,59 @@
+mod activity;
+mod csv_export;
 pub mod db;
+mod dem;
+#[cfg(feature = "duckdb")]
+mod duckdb_db;
+mod encounters;
+mod explorer;
+mod export;
+mod fit;
 mod geojson;
+mod geojsonseq;
+mod geoparquet;
+mod geotag;
 mod gpx;
+mod heatmap;
 mod http;
+mod interpolate;
+mod kml;
 mod mqtt;
+mod mvt;
 mod owntracks;
+mod profile;
+mod projection;
+mod regions;
+mod search;
+mod simplify;
+mod smoothing;
+mod spatial_index;
+mod splits;
+mod stationary;
 mod stats;
+mod tcx;
+mod tiles;
 
+use clap::{Parser, Subcommand};
 use db::Db;
 use env_logger::Env;
 
+#[derive(Parser)]
+#[command(version, about)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Run HTTP server and MQTT client (default)
+    Serve,
+    /// Write XMP sidecar files with GPS positions for photos
+    Geotag(geotag::GeotagArgs),
+    /// Export fixes of a device into a file
+    Export(export::ExportArgs),
+}
+
 #[actix_web::main]
 async fn main() -> anyhow::Result<()> {
     match dotenvy::dotenv() {
@@ -16,9 +61,16 @@ async fn main() -> anyhow::Result<()> {
         Err(err) => anyhow::bail!(err),
     }
     env_logger::init_from_env(Env::default().default_filter_or("info"));
+    let cli = Cli::parse();
 
     let db = Db::connect().await?;
     db.run_migrations().await?;
+    match &cli.command {
+        Some(Command::Geotag(args)) => return geotag::geotag(&db, args).await,
+        Some(Command::Export(args)) => return export::export(&db, args).await,
+        Some(Command::Serve) | None => {}
+    }
+    db.update_region_visits().await?;
     let mqtt_db = db.clone();
     let _handler = tokio::spawn(async move {
         mqtt::subscribe(&mqtt_db).await.unwrap();
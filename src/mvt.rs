@@ -0,0 +1,360 @@
+//! Mapbox Vector Tile encoder (specification version 2).
+
+use std::collections::HashMap;
+use std::f64::consts::PI;
+
+/// Tile extent in tile coordinates
+pub const EXTENT: u32 = 4096;
+
+const LAYER_VERSION: u64 = 2;
+
+/// Geometry types
+const POINT: u64 = 1;
+const LINESTRING: u64 = 2;
+
+/// Geometry commands
+const MOVE_TO: u32 = 1;
+const LINE_TO: u32 = 2;
+
+/// Protobuf wire types
+const VARINT: u64 = 0;
+const LENGTH_DELIMITED: u64 = 2;
+
+/// Feature property value
+pub enum Value {
+    String(String),
+    Int(i64),
+}
+
+/// Tile address in the Web Mercator tiling scheme
+#[derive(Clone, Copy, Debug)]
+pub struct TileCoord {
+    pub z: u8,
+    pub x: u32,
+    pub y: u32,
+}
+
+impl TileCoord {
+    pub fn is_valid(&self) -> bool {
+        self.z <= 30 && self.x < (1 << self.z) && self.y < (1 << self.z)
+    }
+
+    /// Bounding box `[xmin, ymin, xmax, ymax]` in degrees, extended by `buffer` tile units
+    pub fn bbox(&self, buffer: f64) -> [f64; 4] {
+        let n = 2f64.powi(self.z as i32);
+        let lon = |x: f64| x / n * 360.0 - 180.0;
+        let lat = |y: f64| (PI * (1.0 - 2.0 * y / n)).sinh().atan().to_degrees();
+        let (x, y) = (self.x as f64, self.y as f64);
+        [
+            lon(x - buffer).max(-180.0),
+            lat(y + 1.0 + buffer),
+            lon(x + 1.0 + buffer).min(180.0),
+            lat(y - buffer),
+        ]
+    }
+
+    /// Position in tile coordinates
+    pub fn project(&self, lon: f64, lat: f64) -> (f64, f64) {
+        let (x, y) = tile_position(self.z, lon, lat);
+        (
+            (x - self.x as f64) * EXTENT as f64,
+            (y - self.y as f64) * EXTENT as f64,
+        )
+    }
+}
+
+/// Fractional tile numbers of a position at a zoom level
+pub fn tile_position(z: u8, lon: f64, lat: f64) -> (f64, f64) {
+    let n = 2f64.powi(z as i32);
+    let x = (lon + 180.0) / 360.0 * n;
+    let lat = lat.clamp(-85.05113, 85.05113).to_radians();
+    let y = (1.0 - (lat.tan() + 1.0 / lat.cos()).ln() / PI) / 2.0 * n;
+    (x, y)
+}
+
+struct Feature {
+    tags: Vec<u32>,
+    geom_type: u64,
+    geometry: Vec<u32>,
+}
+
+/// Vector tile layer
+pub struct Layer {
+    name: String,
+    features: Vec<Feature>,
+    keys: Vec<String>,
+    key_index: HashMap<String, u32>,
+    /// Encoded values
+    values: Vec<Vec<u8>>,
+    value_index: HashMap<Vec<u8>, u32>,
+}
+
+impl Layer {
+    pub fn new(name: &str) -> Self {
+        Layer {
+            name: name.to_string(),
+            features: Vec::new(),
+            keys: Vec::new(),
+            key_index: HashMap::new(),
+            values: Vec::new(),
+            value_index: HashMap::new(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.features.is_empty()
+    }
+
+    /// Add point feature at tile coordinates
+    pub fn add_point(&mut self, pt: (i32, i32), properties: Vec<(&str, Value)>) {
+        let geometry = vec![command(MOVE_TO, 1), zigzag(pt.0), zigzag(pt.1)];
+        self.add_feature(POINT, geometry, properties);
+    }
+
+    /// Add line feature with tile coordinates
+    pub fn add_line(&mut self, line: &[(i32, i32)], properties: Vec<(&str, Value)>) {
+        let mut line = line.to_vec();
+        line.dedup();
+        if line.len() < 2 {
+            return;
+        }
+        let mut geometry = Vec::with_capacity(2 * line.len() + 2);
+        let mut cursor = (0, 0);
+        for (i, pt) in line.iter().enumerate() {
+            match i {
+                0 => geometry.push(command(MOVE_TO, 1)),
+                1 => geometry.push(command(LINE_TO, line.len() as u32 - 1)),
+                _ => {}
+            }
+            geometry.push(zigzag(pt.0 - cursor.0));
+            geometry.push(zigzag(pt.1 - cursor.1));
+            cursor = *pt;
+        }
+        self.add_feature(LINESTRING, geometry, properties);
+    }
+
+    fn add_feature(&mut self, geom_type: u64, geometry: Vec<u32>, properties: Vec<(&str, Value)>) {
+        let mut tags = Vec::with_capacity(2 * properties.len());
+        for (key, value) in properties {
+            let key_no = match self.key_index.get(key) {
+                Some(no) => *no,
+                None => {
+                    let no = self.keys.len() as u32;
+                    self.keys.push(key.to_string());
+                    self.key_index.insert(key.to_string(), no);
+                    no
+                }
+            };
+            let value = encode_value(&value);
+            let value_no = match self.value_index.get(&value) {
+                Some(no) => *no,
+                None => {
+                    let no = self.values.len() as u32;
+                    self.values.push(value.clone());
+                    self.value_index.insert(value, no);
+                    no
+                }
+            };
+            tags.push(key_no);
+            tags.push(value_no);
+        }
+        self.features.push(Feature {
+            tags,
+            geom_type,
+            geometry,
+        });
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        write_bytes(&mut buf, 1, self.name.as_bytes());
+        for feature in &self.features {
+            let mut feat = Vec::new();
+            if !feature.tags.is_empty() {
+                write_packed(&mut feat, 2, &feature.tags);
+            }
+            write_varint_field(&mut feat, 3, feature.geom_type);
+            write_packed(&mut feat, 4, &feature.geometry);
+            write_bytes(&mut buf, 2, &feat);
+        }
+        for key in &self.keys {
+            write_bytes(&mut buf, 3, key.as_bytes());
+        }
+        for value in &self.values {
+            write_bytes(&mut buf, 4, value);
+        }
+        write_varint_field(&mut buf, 5, EXTENT as u64);
+        write_varint_field(&mut buf, 15, LAYER_VERSION);
+        buf
+    }
+}
+
+/// Clip line to the square `[min, max]` in both axes, returning the parts within
+pub fn clip_line(line: &[(f64, f64)], min: f64, max: f64) -> Vec<Vec<(f64, f64)>> {
+    let mut parts = Vec::new();
+    let mut part: Vec<(f64, f64)> = Vec::new();
+    for segment in line.windows(2) {
+        match clip_segment(segment[0], segment[1], min, max) {
+            Some((a, b)) => {
+                if part.last() != Some(&a) {
+                    if part.len() > 1 {
+                        parts.push(std::mem::take(&mut part));
+                    }
+                    part = vec![a];
+                }
+                part.push(b);
+            }
+            None => {
+                if part.len() > 1 {
+                    parts.push(std::mem::take(&mut part));
+                }
+                part.clear();
+            }
+        }
+    }
+    if part.len() > 1 {
+        parts.push(part);
+    }
+    parts
+}
+
+/// Liang-Barsky line clipping
+fn clip_segment(
+    a: (f64, f64),
+    b: (f64, f64),
+    min: f64,
+    max: f64,
+) -> Option<((f64, f64), (f64, f64))> {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let (mut t0, mut t1) = (0.0, 1.0);
+    for (p, q) in [
+        (-dx, a.0 - min),
+        (dx, max - a.0),
+        (-dy, a.1 - min),
+        (dy, max - a.1),
+    ] {
+        if p == 0.0 {
+            if q < 0.0 {
+                return None;
+            }
+        } else {
+            let r = q / p;
+            if p < 0.0 {
+                if r > t1 {
+                    return None;
+                }
+                t0 = f64::max(t0, r);
+            } else {
+                if r < t0 {
+                    return None;
+                }
+                t1 = f64::min(t1, r);
+            }
+        }
+    }
+    let at = |t: f64| (a.0 + t * dx, a.1 + t * dy);
+    Some((
+        if t0 > 0.0 { at(t0) } else { a },
+        if t1 < 1.0 { at(t1) } else { b },
+    ))
+}
+
+/// Encode non-empty layers as vector tile
+pub fn encode_tile(layers: &[Layer]) -> Vec<u8> {
+    let mut buf = Vec::new();
+    for layer in layers.iter().filter(|layer| !layer.is_empty()) {
+        write_bytes(&mut buf, 3, &layer.encode());
+    }
+    buf
+}
+
+fn command(id: u32, count: u32) -> u32 {
+    (id & 0x7) | (count << 3)
+}
+
+fn zigzag(n: i32) -> u32 {
+    ((n << 1) ^ (n >> 31)) as u32
+}
+
+fn encode_value(value: &Value) -> Vec<u8> {
+    let mut buf = Vec::new();
+    match value {
+        Value::String(s) => write_bytes(&mut buf, 1, s.as_bytes()),
+        // sint_value
+        Value::Int(v) => write_varint_field(&mut buf, 6, ((v << 1) ^ (v >> 63)) as u64),
+    }
+    buf
+}
+
+fn write_varint(buf: &mut Vec<u8>, mut v: u64) {
+    while v >= 0x80 {
+        buf.push((v as u8 & 0x7f) | 0x80);
+        v >>= 7;
+    }
+    buf.push(v as u8);
+}
+
+fn write_varint_field(buf: &mut Vec<u8>, field: u64, v: u64) {
+    write_varint(buf, (field << 3) | VARINT);
+    write_varint(buf, v);
+}
+
+fn write_bytes(buf: &mut Vec<u8>, field: u64, bytes: &[u8]) {
+    write_varint(buf, (field << 3) | LENGTH_DELIMITED);
+    write_varint(buf, bytes.len() as u64);
+    buf.extend_from_slice(bytes);
+}
+
+fn write_packed(buf: &mut Vec<u8>, field: u64, values: &[u32]) {
+    let mut packed = Vec::with_capacity(values.len());
+    for v in values {
+        write_varint(&mut packed, *v as u64);
+    }
+    write_bytes(buf, field, &packed);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zigzag_encoding() {
+        assert_eq!(zigzag(0), 0);
+        assert_eq!(zigzag(-1), 1);
+        assert_eq!(zigzag(1), 2);
+        assert_eq!(zigzag(-2), 3);
+        assert_eq!(zigzag(i32::MAX), u32::MAX - 1);
+        assert_eq!(zigzag(i32::MIN), u32::MAX);
+    }
+
+    #[test]
+    fn command_encoding() {
+        assert_eq!(command(MOVE_TO, 1), 9);
+        assert_eq!(command(LINE_TO, 3), 26);
+    }
+
+    #[test]
+    fn line_geometry() {
+        // Example from the vector tile specification
+        let mut layer = Layer::new("tracks");
+        layer.add_line(&[(2, 2), (2, 10), (10, 10), (10, 10)], vec![]);
+        assert_eq!(layer.features[0].geometry, [9, 4, 4, 18, 0, 16, 16, 0]);
+        layer.add_line(&[(5, 5), (5, 5)], vec![]);
+        assert_eq!(layer.features.len(), 1);
+    }
+
+    #[test]
+    fn varint_encoding() {
+        let mut buf = Vec::new();
+        write_varint(&mut buf, 300);
+        assert_eq!(buf, [0xac, 0x02]);
+    }
+
+    #[test]
+    fn clip_crossing_line() {
+        let parts = clip_line(&[(-10.0, 5.0), (5.0, 5.0), (20.0, 5.0)], 0.0, 10.0);
+        assert_eq!(parts, vec![vec![(0.0, 5.0), (5.0, 5.0), (10.0, 5.0)]]);
+        let parts = clip_line(&[(-10.0, -5.0), (20.0, -5.0)], 0.0, 10.0);
+        assert!(parts.is_empty());
+    }
+}
@@ -105,7 +105,7 @@ pub struct OtLocation {
 }
 
 /// OwnTracks compatible location with custom annotations
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Location {
     /// Tracker ID used to display the initials of a user (iOS,Android/string/optional) required for http mode
     #[serde(default)] // Make optional regarding to spec
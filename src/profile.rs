@@ -0,0 +1,145 @@
+use crate::db::GpsPoint;
+use crate::geojson::MAX_ACCURACY;
+use crate::stats::DistanceStats;
+use serde::Serialize;
+
+/// Maximal number of resampled points
+const MAX_SAMPLES: usize = 10_000;
+
+/// Distance indexed track profile
+#[derive(Serialize, Default, Debug)]
+pub struct Profile {
+    /// Start time of track
+    pub ts_start: Option<String>,
+    /// Distance from start (meters)
+    pub distance: Vec<f64>,
+    /// Elevation (meters)
+    pub elevation: Vec<Option<f64>>,
+    /// Speed (km/h)
+    pub speed: Vec<Option<f64>>,
+    /// Time since start (seconds)
+    pub time: Vec<Option<f64>>,
+}
+
+/// Build elevation and speed profile, optionally resampled to equidistant points.
+///
+/// The number of resampled points is limited to `MAX_SAMPLES`.
+pub fn profile(points: &[GpsPoint], samples: Option<usize>) -> Profile {
+    let points: Vec<&GpsPoint> = points
+        .iter()
+        .filter(|point| {
+            // keep only points within accuracy
+            point.accuracy.unwrap_or(0) < MAX_ACCURACY
+        })
+        .collect();
+    let distance_stats = DistanceStats::from_xy_iter(points.iter().map(|pt| (pt.x, pt.y)));
+    let distance = distance_stats.cumulative().to_vec();
+    let epochs: Vec<Option<i64>> = points.iter().map(|pt| pt.epoch()).collect();
+    let t0 = epochs.iter().flatten().next().copied();
+    let time: Vec<Option<f64>> = epochs
+        .iter()
+        .map(|ts| t0.zip(*ts).map(|(t0, ts)| (ts - t0) as f64))
+        .collect();
+    let speed = (0..points.len())
+        .map(|i| {
+            points[i].speed.map(f64::from).or_else(|| {
+                // Derive speed from previous point
+                let i0 = i.checked_sub(1)?;
+                let dt = time[i]? - time[i0]?;
+                (dt > 0.0).then(|| (distance[i] - distance[i0]) / dt * 3.6)
+            })
+        })
+        .collect();
+    let profile = Profile {
+        ts_start: points.first().map(|pt| pt.ts.clone()),
+        elevation: points
+            .iter()
+            .map(|pt| pt.elevation.map(f64::from))
+            .collect(),
+        distance,
+        speed,
+        time,
+    };
+    match samples {
+        Some(n) if n >= 2 && profile.distance.len() >= 2 => resample(profile, n.min(MAX_SAMPLES)),
+        _ => profile,
+    }
+}
+
+/// Resample profile to `n` equidistant points with linear interpolation
+fn resample(profile: Profile, n: usize) -> Profile {
+    let total = *profile.distance.last().unwrap_or(&0.0);
+    let mut resampled = Profile {
+        ts_start: profile.ts_start.clone(),
+        ..Default::default()
+    };
+    let mut i = 0;
+    for k in 0..n {
+        let d = total * k as f64 / (n - 1) as f64;
+        while i + 2 < profile.distance.len() && profile.distance[i + 1] < d {
+            i += 1;
+        }
+        let (d0, d1) = (profile.distance[i], profile.distance[i + 1]);
+        let t = if d1 > d0 {
+            ((d - d0) / (d1 - d0)).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        resampled.distance.push(d);
+        resampled
+            .elevation
+            .push(interpolate(&profile.elevation, i, t));
+        resampled.speed.push(interpolate(&profile.speed, i, t));
+        resampled.time.push(interpolate(&profile.time, i, t));
+    }
+    resampled
+}
+
+/// Interpolate between values `i` and `i + 1`, falling back to the nearest value
+fn interpolate(values: &[Option<f64>], i: usize, t: f64) -> Option<f64> {
+    match (values[i], values[i + 1]) {
+        (Some(v0), Some(v1)) => Some(v0 + (v1 - v0) * t),
+        (v0, v1) if t < 0.5 => v0.or(v1),
+        (v0, v1) => v1.or(v0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn points() -> Vec<GpsPoint> {
+        // 0.009° at the equator is about 1 km
+        [(0.0, 100), (0.009, 200), (0.018, 100)]
+            .iter()
+            .enumerate()
+            .map(|(i, (x, elevation))| GpsPoint {
+                x: *x,
+                ts: format!("2025-10-09 08:{:02}:00", i * 5),
+                elevation: Some(*elevation),
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn resampled_profile() {
+        let profile = profile(&points(), Some(5));
+        assert_eq!(profile.distance.len(), 5);
+        let elevation: Vec<i64> = profile
+            .elevation
+            .iter()
+            .map(|ele| ele.unwrap().round() as i64)
+            .collect();
+        assert_eq!(elevation, vec![100, 150, 200, 150, 100]);
+        assert_eq!(profile.time[2].map(f64::round), Some(300.0));
+        // 1 km in 5 minutes
+        assert_eq!(profile.speed[1].map(f64::round), Some(12.0));
+    }
+
+    #[test]
+    fn limit_samples() {
+        let profile = profile(&points(), Some(1_000_000_000));
+        assert_eq!(profile.distance.len(), MAX_SAMPLES);
+    }
+}
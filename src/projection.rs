@@ -0,0 +1,84 @@
+/// Mean earth radius (meters)
+pub const EARTH_RADIUS: f64 = 6_371_008.8;
+/// Sphere radius of Web Mercator (meters)
+const EARTH_RADIUS_WEB_MERCATOR: f64 = 6_378_137.0;
+
+/// Equirectangular projection to local metric coordinates around an origin
+#[derive(Clone, Copy, Debug)]
+pub struct LocalProjection {
+    lon0: f64,
+    lat0: f64,
+    scale_x: f64,
+    scale_y: f64,
+}
+
+impl LocalProjection {
+    pub fn new(lon0: f64, lat0: f64) -> Self {
+        let scale_y = EARTH_RADIUS.to_radians();
+        let scale_x = scale_y * lat0.to_radians().cos();
+        LocalProjection {
+            lon0,
+            lat0,
+            scale_x,
+            scale_y,
+        }
+    }
+    /// Project WGS84 coordinates to meters
+    pub fn project(&self, lon: f64, lat: f64) -> (f64, f64) {
+        (
+            (lon - self.lon0) * self.scale_x,
+            (lat - self.lat0) * self.scale_y,
+        )
+    }
+    /// Convert meters to WGS84 coordinates
+    pub fn unproject(&self, x: f64, y: f64) -> (f64, f64) {
+        (self.lon0 + x / self.scale_x, self.lat0 + y / self.scale_y)
+    }
+}
+
+/// Spherical Web Mercator projection (EPSG:3857)
+#[derive(Clone, Copy, Debug)]
+pub struct WebMercator;
+
+impl WebMercator {
+    /// Latitude limit of the projection
+    pub const MAX_LAT: f64 = 85.051_128_78;
+
+    /// Project WGS84 coordinates to Web Mercator meters
+    pub fn project(lon: f64, lat: f64) -> (f64, f64) {
+        let lat = lat.clamp(-Self::MAX_LAT, Self::MAX_LAT).to_radians();
+        (
+            EARTH_RADIUS_WEB_MERCATOR * lon.to_radians(),
+            EARTH_RADIUS_WEB_MERCATOR * (std::f64::consts::FRAC_PI_4 + lat / 2.0).tan().ln(),
+        )
+    }
+    /// Convert Web Mercator meters to WGS84 coordinates
+    pub fn unproject(x: f64, y: f64) -> (f64, f64) {
+        (
+            (x / EARTH_RADIUS_WEB_MERCATOR).to_degrees(),
+            (2.0 * (y / EARTH_RADIUS_WEB_MERCATOR).exp().atan() - std::f64::consts::FRAC_PI_2)
+                .to_degrees(),
+        )
+    }
+    /// Scale factor of Web Mercator meters to ground meters at a latitude
+    pub fn scale(lat: f64) -> f64 {
+        1.0 / lat.clamp(-Self::MAX_LAT, Self::MAX_LAT).to_radians().cos()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn web_mercator_roundtrip() {
+        let (x, y) = WebMercator::project(180.0, 0.0);
+        assert!((x - 20_037_508.34).abs() < 0.01);
+        assert!(y.abs() < 1e-6);
+        let (x, y) = WebMercator::project(9.435, 47.05);
+        let (lon, lat) = WebMercator::unproject(x, y);
+        assert!((lon - 9.435).abs() < 1e-9);
+        assert!((lat - 47.05).abs() < 1e-9);
+        assert!((WebMercator::scale(60.0) - 2.0).abs() < 1e-9);
+    }
+}
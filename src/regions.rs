@@ -0,0 +1,219 @@
+use geo::{BoundingRect, Contains, MultiPolygon, Point, Rect};
+use geozero::wkb;
+use sqlx::sqlite::SqliteConnectOptions;
+use sqlx::{ConnectOptions, Connection};
+use std::path::{Path, PathBuf};
+
+/// Country or region boundaries from a local GeoJSON or GeoPackage file (WGS84)
+pub struct Boundaries {
+    /// Boundary file and name field the regions were read from
+    source: String,
+    regions: Vec<Region>,
+}
+
+struct Region {
+    name: String,
+    bbox: Rect,
+    geometry: MultiPolygon,
+}
+
+impl Boundaries {
+    pub async fn from_env() -> anyhow::Result<Option<Self>> {
+        let Some(path) = dotenvy::var("BOUNDARY_FILE")
+            .ok()
+            .filter(|path| !path.is_empty())
+        else {
+            return Ok(None);
+        };
+        let name_field = dotenvy::var("BOUNDARY_NAME_FIELD").unwrap_or("name".to_string());
+        let path = PathBuf::from(path);
+        let features = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("gpkg") => read_gpkg(&path, &name_field).await?,
+            _ => read_geojson(&path, &name_field)?,
+        };
+        let boundaries = Self::new(format!("{}#{name_field}", path.display()), features);
+        log::info!(
+            "Using {} region boundaries from `{}`",
+            boundaries.regions.len(),
+            path.display()
+        );
+        Ok(Some(boundaries))
+    }
+
+    /// Boundaries from named features, skipping unnamed and non-polygon features
+    pub fn new(source: String, features: Vec<(Option<String>, geo::Geometry)>) -> Self {
+        let regions = features
+            .into_iter()
+            .filter_map(|(name, geometry)| Region::new(name, geometry))
+            .collect();
+        Boundaries { source, regions }
+    }
+
+    /// Identifier of the boundary file and name field
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Names of regions containing a position
+    pub fn regions_at(&self, x: f64, y: f64) -> impl Iterator<Item = &str> {
+        let point = Point::new(x, y);
+        self.regions
+            .iter()
+            .filter(move |region| region.bbox.contains(&point) && region.geometry.contains(&point))
+            .map(|region| region.name.as_str())
+    }
+}
+
+impl Region {
+    fn new(name: Option<String>, geometry: geo::Geometry) -> Option<Self> {
+        let geometry = match geometry {
+            geo::Geometry::Polygon(polygon) => MultiPolygon::new(vec![polygon]),
+            geo::Geometry::MultiPolygon(multi_polygon) => multi_polygon,
+            _ => return None,
+        };
+        Some(Region {
+            name: name.filter(|name| !name.is_empty())?,
+            bbox: geometry.bounding_rect()?,
+            geometry,
+        })
+    }
+}
+
+fn read_geojson(
+    path: &Path,
+    name_field: &str,
+) -> anyhow::Result<Vec<(Option<String>, geo::Geometry)>> {
+    parse_geojson(&std::fs::read_to_string(path)?, name_field)
+}
+
+fn parse_geojson(
+    geojson: &str,
+    name_field: &str,
+) -> anyhow::Result<Vec<(Option<String>, geo::Geometry)>> {
+    let geojson: geojson::GeoJson = geojson.parse()?;
+    let collection = geojson::FeatureCollection::try_from(geojson)?;
+    let features = collection
+        .features
+        .into_iter()
+        .filter_map(|feature| {
+            let name = feature.property(name_field).map(|value| match value {
+                serde_json::Value::String(name) => name.clone(),
+                value => value.to_string(),
+            });
+            let geometry = geo::Geometry::try_from(feature.geometry?.value).ok()?;
+            Some((name, geometry))
+        })
+        .collect();
+    Ok(features)
+}
+
+async fn read_gpkg(
+    path: &Path,
+    name_field: &str,
+) -> anyhow::Result<Vec<(Option<String>, geo::Geometry)>> {
+    let mut conn = SqliteConnectOptions::new()
+        .filename(path)
+        .read_only(true)
+        .connect()
+        .await?;
+    let tables: Vec<(String, String, i32)> =
+        sqlx::query_as("SELECT table_name, column_name, srs_id FROM gpkg_geometry_columns")
+            .fetch_all(&mut conn)
+            .await?;
+    let mut features = Vec::new();
+    for (table, column, srs_id) in tables {
+        if srs_id != 4326 {
+            anyhow::bail!("Boundaries of table `{table}` are not in WGS84 (EPSG:4326)");
+        }
+        let columns: Vec<String> = sqlx::query_scalar("SELECT name FROM pragma_table_info($1)")
+            .bind(&table)
+            .fetch_all(&mut conn)
+            .await?;
+        if !columns
+            .iter()
+            .any(|name| name.eq_ignore_ascii_case(name_field))
+        {
+            anyhow::bail!("Table `{table}` has no name field `{name_field}`");
+        }
+        let (name_field, column, table) = (
+            quote_identifier(name_field),
+            quote_identifier(&column),
+            quote_identifier(&table),
+        );
+        let rows: Vec<(Option<String>, wkb::Decode<geo::Geometry>)> = sqlx::query_as(&format!(
+            r#"SELECT CAST({name_field} AS TEXT), {column} FROM {table} WHERE {column} IS NOT NULL"#
+        ))
+        .fetch_all(&mut conn)
+        .await?;
+        features.extend(
+            rows.into_iter()
+                .filter_map(|(name, geometry)| Some((name, geometry.geometry?))),
+        );
+    }
+    conn.close().await?;
+    Ok(features)
+}
+
+/// Quote an SQL identifier, escaping embedded quotes
+fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Two overlapping squares, a point and an unnamed square
+    pub(crate) const FEATURES: &str = r#"{
+        "type": "FeatureCollection",
+        "features": [
+            {"type": "Feature", "properties": {"name": "A", "id": 1},
+             "geometry": {"type": "Polygon", "coordinates": [[[0, 0], [2, 0], [2, 2], [0, 2], [0, 0]]]}},
+            {"type": "Feature", "properties": {"name": "B", "id": 2},
+             "geometry": {"type": "MultiPolygon", "coordinates": [[[[1, 1], [3, 1], [1, 3], [1, 1]]]]}},
+            {"type": "Feature", "properties": {"name": "P", "id": 3},
+             "geometry": {"type": "Point", "coordinates": [0.5, 0.5]}},
+            {"type": "Feature", "properties": {"id": 4},
+             "geometry": {"type": "Polygon", "coordinates": [[[5, 5], [6, 5], [6, 6], [5, 5]]]}}
+        ]
+    }"#;
+
+    pub(crate) fn boundaries(source: &str, name_field: &str) -> Boundaries {
+        Boundaries::new(
+            source.to_string(),
+            parse_geojson(FEATURES, name_field).unwrap(),
+        )
+    }
+
+    #[test]
+    fn geojson_features() {
+        let features = parse_geojson(FEATURES, "name").unwrap();
+        let names: Vec<_> = features.iter().map(|(name, _)| name.as_deref()).collect();
+        assert_eq!(names, [Some("A"), Some("B"), Some("P"), None]);
+        let ids: Vec<_> = parse_geojson(FEATURES, "id")
+            .unwrap()
+            .into_iter()
+            .map(|(name, _)| name.unwrap())
+            .collect();
+        assert_eq!(ids, ["1", "2", "3", "4"]);
+        assert!(parse_geojson(r#"{"type": "Point", "coordinates": [0, 0]}"#, "name").is_err());
+    }
+
+    #[test]
+    fn regions_containing_position() {
+        let boundaries = boundaries("test", "name");
+        assert_eq!(boundaries.regions.len(), 2);
+        let regions = |x, y| boundaries.regions_at(x, y).collect::<Vec<_>>();
+        assert_eq!(regions(0.5, 0.5), ["A"]);
+        assert_eq!(regions(1.5, 1.2), ["A", "B"]);
+        // Within the bounding box of B, outside of the triangle
+        assert_eq!(regions(2.8, 2.8), Vec::<&str>::new());
+        assert_eq!(regions(5.9, 5.1), Vec::<&str>::new());
+    }
+
+    #[test]
+    fn quoted_identifiers() {
+        assert_eq!(quote_identifier("name"), r#""name""#);
+        assert_eq!(quote_identifier(r#"na"me"#), r#""na""me""#);
+    }
+}
@@ -0,0 +1,184 @@
+use crate::db::{AreaFilter, AreaFix, AreaPassage, Db, TrackInfo};
+use geo::{BoundingRect, Intersects, Point};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Default maximal number of returned passages
+const DEFAULT_LIMIT: usize = 100;
+/// Upper limit of returned passages
+const MAX_LIMIT: usize = 1000;
+
+#[derive(Deserialize, Debug)]
+pub struct SpatialSearchParams {
+    /// Bounding box `xmin,ymin,xmax,ymax`
+    pub bbox: Option<String>,
+    /// First date in format 2025-02-19
+    pub date_from: Option<String>,
+    /// Last date in format 2025-02-19
+    pub date_to: Option<String>,
+    pub device_id: Option<i32>,
+    /// Maximal number of passages (max. 1000)
+    pub limit: Option<usize>,
+}
+
+/// Track passing through a search area
+#[derive(Serialize, Debug)]
+pub struct TrackPassage {
+    #[serde(flatten)]
+    pub track: TrackInfo,
+    /// First fix within area
+    pub ts_enter: String,
+    /// Last fix within area
+    pub ts_leave: String,
+}
+
+/// Parse bounding box parameter `xmin,ymin,xmax,ymax`
+pub fn parse_bbox(bbox: &str) -> Option<[f64; 4]> {
+    let coords: Vec<f64> = bbox
+        .split(',')
+        .map(|v| v.trim().parse().ok())
+        .collect::<Option<_>>()?;
+    let [xmin, ymin, xmax, ymax] = coords.try_into().ok()?;
+    (xmin <= xmax && ymin <= ymax).then_some([xmin, ymin, xmax, ymax])
+}
+
+/// Find tracks passing through a bounding box or area, most recent first
+pub async fn spatial_search(
+    db: &Db,
+    params: &SpatialSearchParams,
+    area: Option<&geo::Geometry>,
+) -> anyhow::Result<Vec<TrackPassage>> {
+    let bbox = match (params.bbox.as_deref(), area) {
+        (Some(bbox), _) => parse_bbox(bbox).ok_or(anyhow::anyhow!("invalid bbox"))?,
+        (None, Some(area)) => {
+            let rect = area
+                .bounding_rect()
+                .ok_or(anyhow::anyhow!("empty search area"))?;
+            [rect.min().x, rect.min().y, rect.max().x, rect.max().y]
+        }
+        (None, None) => anyhow::bail!("bbox or area required"),
+    };
+    let filter = AreaFilter {
+        bbox,
+        date_from: params.date_from.clone(),
+        date_to: params.date_to.clone(),
+        device_id: params.device_id,
+        area: area
+            .filter(|_| db.postgis())
+            .map(|area| ::geojson::Geometry::new(::geojson::Value::from(area)).to_string()),
+    };
+    let limit = params.limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT);
+    let passages = match area.filter(|_| !db.postgis()) {
+        // Without PostGIS the area is tested here
+        Some(area) => area_passages(db.query_area_fixes(&filter).await?, area, limit),
+        None => db.query_area_passages(&filter, limit).await?,
+    };
+
+    let mut dates: Vec<&str> = passages
+        .iter()
+        .map(|passage| passage.date.as_str())
+        .collect();
+    dates.sort();
+    dates.dedup();
+    let mut track_infos: HashMap<(i32, String), TrackInfo> = db
+        .query_dates_tracks_info(&dates)
+        .await?
+        .into_iter()
+        .map(|(date, info)| ((info.device_id, date), info))
+        .collect();
+    let result = passages
+        .into_iter()
+        .filter_map(|passage| {
+            track_infos
+                .remove(&(passage.device_id, passage.date))
+                .map(|track| TrackPassage {
+                    track,
+                    ts_enter: passage.ts_enter,
+                    ts_leave: passage.ts_leave,
+                })
+        })
+        .collect();
+    Ok(result)
+}
+
+/// First and last fix within an area per device and day, most recent first
+fn area_passages(fixes: Vec<AreaFix>, area: &geo::Geometry, limit: usize) -> Vec<AreaPassage> {
+    let mut passages: Vec<AreaPassage> = Vec::new();
+    // Fixes are ordered by device and time
+    for fix in fixes {
+        if !area.intersects(&Point::new(fix.x, fix.y)) {
+            continue;
+        }
+        match passages.last_mut() {
+            Some(passage) if passage.device_id == fix.device_id && passage.date == fix.date => {
+                passage.ts_leave = fix.ts;
+            }
+            _ => passages.push(AreaPassage {
+                device_id: fix.device_id,
+                date: fix.date,
+                ts_enter: fix.ts.clone(),
+                ts_leave: fix.ts,
+            }),
+        }
+    }
+    passages.sort_by(|a, b| {
+        b.ts_enter
+            .cmp(&a.ts_enter)
+            .then(a.device_id.cmp(&b.device_id))
+    });
+    passages.truncate(limit);
+    passages
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use geo::{polygon, Geometry};
+
+    fn fix(device_id: i32, x: f64, ts: &str) -> AreaFix {
+        AreaFix {
+            device_id,
+            y: 0.5,
+            x,
+            ts: ts.to_string(),
+            date: ts[..10].to_string(),
+        }
+    }
+
+    #[test]
+    fn passages_within_area() {
+        let area = Geometry::Polygon(polygon![
+            (x: 0.0, y: 0.0),
+            (x: 1.0, y: 0.0),
+            (x: 1.0, y: 1.0),
+            (x: 0.0, y: 1.0),
+        ]);
+        let fixes = vec![
+            fix(1, 0.5, "2025-10-08 08:00:00"),
+            fix(1, 0.6, "2025-10-08 09:00:00"),
+            fix(1, 2.0, "2025-10-08 10:00:00"),
+            fix(1, 0.5, "2025-10-09 08:00:00"),
+            fix(2, 2.0, "2025-10-09 07:00:00"),
+            fix(2, 0.5, "2025-10-09 08:00:00"),
+        ];
+        let passages = area_passages(fixes, &area, 10);
+        assert_eq!(passages.len(), 3);
+        assert_eq!(
+            (passages[2].ts_enter.as_str(), passages[2].ts_leave.as_str()),
+            ("2025-10-08 08:00:00", "2025-10-08 09:00:00")
+        );
+        let passages = &passages[..2];
+        let passages: Vec<_> = passages
+            .iter()
+            .map(|p| (p.device_id, p.ts_enter.as_str(), p.ts_leave.as_str()))
+            .collect();
+        assert_eq!(
+            passages,
+            [
+                (1, "2025-10-09 08:00:00", "2025-10-09 08:00:00"),
+                (2, "2025-10-09 08:00:00", "2025-10-09 08:00:00"),
+            ]
+        );
+        assert_eq!(area_passages(Vec::new(), &area, 10), []);
+    }
+}
@@ -0,0 +1,122 @@
+use crate::db::GpsPoint;
+use crate::geojson::MAX_ACCURACY;
+use crate::projection::LocalProjection;
+use geo::{LineString, SimplifyIdx, SimplifyVwIdx};
+use serde::Deserialize;
+
+/// Default simplification tolerance (meters)
+const DEFAULT_TOLERANCE: f64 = 5.0;
+/// Meters per pixel at zoom level 0 on the equator (512px tiles as used by MapLibre)
+const ZOOM0_RESOLUTION: f64 = 78271.517;
+
+/// Line simplification algorithm
+#[derive(Deserialize, Clone, Copy, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Simplification {
+    /// Douglas-Peucker
+    Dp,
+    /// Visvalingam-Whyatt
+    Vw,
+}
+
+/// Tolerance of one pixel at a given zoom level and latitude (meters)
+pub fn zoom_tolerance(zoom: u8, lat: f64) -> f64 {
+    ZOOM0_RESOLUTION * lat.to_radians().cos() / 2f64.powi(zoom as i32)
+}
+
+/// Simplify track points with a tolerance in meters.
+///
+/// Without explicit tolerance, it is derived from the zoom level or a default is used.
+/// Points outside of accuracy are dropped first, so that outliers don't become vertices.
+pub fn simplify(
+    mut points: Vec<GpsPoint>,
+    method: Simplification,
+    tolerance: Option<f64>,
+    zoom: Option<u8>,
+) -> Vec<GpsPoint> {
+    points.retain(|point| point.accuracy.unwrap_or(0) < MAX_ACCURACY);
+    let Some(first) = points.first() else {
+        return points;
+    };
+    let tolerance = tolerance
+        .or(zoom.map(|z| zoom_tolerance(z, first.y)))
+        .unwrap_or(DEFAULT_TOLERANCE);
+    let proj = LocalProjection::new(first.x, first.y);
+    let line: LineString = points.iter().map(|pt| proj.project(pt.x, pt.y)).collect();
+    let indices = match method {
+        Simplification::Dp => line.simplify_idx(&tolerance),
+        Simplification::Vw => line.simplify_vw_idx(&(tolerance * tolerance)),
+    };
+    let mut points: Vec<Option<GpsPoint>> = points.into_iter().map(Some).collect();
+    indices
+        .into_iter()
+        .filter_map(|idx| points[idx].take())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Straight line to the east with a small bump in the middle
+    fn line(bump: f64, accuracy: i32) -> (LocalProjection, Vec<GpsPoint>) {
+        let proj = LocalProjection::new(9.43, 47.05);
+        let points = (0..11)
+            .map(|i| {
+                let (y, accuracy) = if i == 5 { (bump, accuracy) } else { (0.0, 5) };
+                let (x, y) = proj.unproject(i as f64 * 100.0, y);
+                GpsPoint {
+                    x,
+                    y,
+                    accuracy: Some(accuracy),
+                    ..Default::default()
+                }
+            })
+            .collect();
+        (proj, points)
+    }
+
+    /// Offsets from the straight line of simplified points (meters)
+    fn offsets(proj: &LocalProjection, points: &[GpsPoint]) -> Vec<i64> {
+        points
+            .iter()
+            .map(|pt| proj.project(pt.x, pt.y).1.round() as i64)
+            .collect()
+    }
+
+    #[test]
+    fn douglas_peucker() {
+        let (proj, points) = line(2.0, 5);
+        let simplified = simplify(points, Simplification::Dp, None, None);
+        assert_eq!(offsets(&proj, &simplified), vec![0, 0]);
+        let (proj, points) = line(20.0, 5);
+        let simplified = simplify(points, Simplification::Dp, Some(10.0), None);
+        assert_eq!(offsets(&proj, &simplified), vec![0, 0, 20, 0, 0]);
+    }
+
+    #[test]
+    fn visvalingam() {
+        // Triangle area of the bump: 200m * 20m / 2 = 2000m²
+        let (proj, points) = line(20.0, 5);
+        let simplified = simplify(points, Simplification::Vw, Some(50.0), None);
+        assert_eq!(offsets(&proj, &simplified), vec![0, 0]);
+        let (proj, points) = line(20.0, 5);
+        let simplified = simplify(points, Simplification::Vw, Some(40.0), None);
+        assert_eq!(offsets(&proj, &simplified), vec![0, 0, 20, 0, 0]);
+    }
+
+    #[test]
+    fn drop_inaccurate_points() {
+        let (_, points) = line(500.0, 1000);
+        assert_eq!(
+            simplify(points, Simplification::Dp, Some(10.0), None).len(),
+            2
+        );
+    }
+
+    #[test]
+    fn zoom() {
+        assert!((zoom_tolerance(0, 0.0) - ZOOM0_RESOLUTION).abs() < 1e-6);
+        assert!((zoom_tolerance(1, 60.0) - ZOOM0_RESOLUTION / 4.0).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,220 @@
+use crate::db::GpsPoint;
+use crate::projection::LocalProjection;
+use serde::Deserialize;
+
+/// Measurement noise for fixes without accuracy (meters)
+const DEFAULT_ACCURACY: f64 = 10.0;
+/// Process noise: standard deviation of acceleration (m/s²)
+const ACCELERATION_NOISE: f64 = 0.2;
+
+/// Track smoothing method
+#[derive(Deserialize, Clone, Copy, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Smoothing {
+    /// Kalman filter with Rauch-Tung-Striebel smoother
+    Kalman,
+}
+
+/// Smooth track points in place
+pub fn smooth(points: &mut [GpsPoint], method: Smoothing) {
+    match method {
+        Smoothing::Kalman => kalman_smooth(points),
+    }
+}
+
+/// 2x2 covariance matrix [[a, b], [b, c]]
+#[derive(Clone, Copy, Default)]
+struct Cov {
+    a: f64,
+    b: f64,
+    c: f64,
+}
+
+/// Position and velocity of one axis
+#[derive(Clone, Copy, Default)]
+struct AxisState {
+    pos: f64,
+    vel: f64,
+}
+
+/// Constant velocity model applied to both axes of a local metric projection.
+///
+/// Both axes share the same noise model, so the covariance is computed once and only the
+/// state means differ per axis.
+fn kalman_smooth(points: &mut [GpsPoint]) {
+    if points.len() < 3 {
+        return;
+    }
+    let proj = LocalProjection::new(points[0].x, points[0].y);
+    let measurements: Vec<(f64, f64)> = points.iter().map(|pt| proj.project(pt.x, pt.y)).collect();
+    let times: Vec<Option<i64>> = points.iter().map(|pt| pt.epoch()).collect();
+    let q = ACCELERATION_NOISE.powi(2);
+
+    let n = points.len();
+    let mut dts = vec![0.0; n];
+    let mut predicted_cov = vec![Cov::default(); n];
+    let mut predicted = vec![(AxisState::default(), AxisState::default()); n];
+    let mut filtered_cov = vec![Cov::default(); n];
+    let mut filtered = vec![(AxisState::default(), AxisState::default()); n];
+
+    // Forward pass
+    for k in 0..n {
+        let r = points[k]
+            .accuracy
+            .filter(|acc| *acc > 0)
+            .map(|acc| acc as f64)
+            .unwrap_or(DEFAULT_ACCURACY)
+            .powi(2);
+        let (state, cov) = if k == 0 {
+            let state = (
+                AxisState {
+                    pos: measurements[0].0,
+                    vel: 0.0,
+                },
+                AxisState {
+                    pos: measurements[0].1,
+                    vel: 0.0,
+                },
+            );
+            // Unknown initial velocity
+            (
+                state,
+                Cov {
+                    a: r,
+                    b: 0.0,
+                    c: 100.0,
+                },
+            )
+        } else {
+            let dt = match (times[k - 1], times[k]) {
+                (Some(t0), Some(t1)) if t1 > t0 => (t1 - t0) as f64,
+                _ => 0.0,
+            };
+            dts[k] = dt;
+            let p = filtered_cov[k - 1];
+            let cov = Cov {
+                a: p.a + 2.0 * dt * p.b + dt * dt * p.c + q * dt.powi(4) / 4.0,
+                b: p.b + dt * p.c + q * dt.powi(3) / 2.0,
+                c: p.c + q * dt * dt,
+            };
+            let (sx, sy) = filtered[k - 1];
+            let state = (predict(sx, dt), predict(sy, dt));
+            (state, cov)
+        };
+        predicted[k] = state;
+        predicted_cov[k] = cov;
+
+        // Measurement update
+        let s = cov.a + r;
+        let (k_pos, k_vel) = (cov.a / s, cov.b / s);
+        let update = |st: AxisState, z: f64| {
+            let innovation = z - st.pos;
+            AxisState {
+                pos: st.pos + k_pos * innovation,
+                vel: st.vel + k_vel * innovation,
+            }
+        };
+        filtered[k] = (
+            update(state.0, measurements[k].0),
+            update(state.1, measurements[k].1),
+        );
+        filtered_cov[k] = Cov {
+            a: (1.0 - k_pos) * cov.a,
+            b: (1.0 - k_pos) * cov.b,
+            c: cov.c - k_vel * cov.b,
+        };
+    }
+
+    // Rauch-Tung-Striebel backward pass
+    let mut smoothed = filtered.clone();
+    for k in (0..n - 1).rev() {
+        let dt = dts[k + 1];
+        let p = filtered_cov[k];
+        let pp = predicted_cov[k + 1];
+        let det = pp.a * pp.c - pp.b * pp.b;
+        if det.abs() < f64::EPSILON {
+            continue;
+        }
+        // P_k F^T
+        let (m00, m01) = (p.a + dt * p.b, p.b);
+        let (m10, m11) = (p.b + dt * p.c, p.c);
+        // Gain C = P_k F^T P_{k+1|k}^-1
+        let (i00, i01, i11) = (pp.c / det, -pp.b / det, pp.a / det);
+        let (c00, c01) = (m00 * i00 + m01 * i01, m00 * i01 + m01 * i11);
+        let (c10, c11) = (m10 * i00 + m11 * i01, m10 * i01 + m11 * i11);
+        let correct = |st: AxisState, next: AxisState, pred: AxisState| {
+            let (dp, dv) = (next.pos - pred.pos, next.vel - pred.vel);
+            AxisState {
+                pos: st.pos + c00 * dp + c01 * dv,
+                vel: st.vel + c10 * dp + c11 * dv,
+            }
+        };
+        smoothed[k] = (
+            correct(filtered[k].0, smoothed[k + 1].0, predicted[k + 1].0),
+            correct(filtered[k].1, smoothed[k + 1].1, predicted[k + 1].1),
+        );
+    }
+
+    for (pt, (sx, sy)) in points.iter_mut().zip(smoothed) {
+        (pt.x, pt.y) = proj.unproject(sx.pos, sy.pos);
+    }
+}
+
+fn predict(state: AxisState, dt: f64) -> AxisState {
+    AxisState {
+        pos: state.pos + dt * state.vel,
+        vel: state.vel,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Points moving east with 5 m/s, disturbed by alternating lateral noise
+    fn noisy_track(n: usize) -> (LocalProjection, Vec<GpsPoint>) {
+        let proj = LocalProjection::new(9.43, 47.05);
+        let points = (0..n)
+            .map(|i| {
+                let noise = if i % 2 == 0 { 8.0 } else { -8.0 };
+                let (x, y) = proj.unproject(i as f64 * 50.0, noise);
+                GpsPoint {
+                    x,
+                    y,
+                    ts: format!("2025-10-09 08:{:02}:{:02}", i / 6, i % 6 * 10),
+                    accuracy: Some(10),
+                    ..Default::default()
+                }
+            })
+            .collect();
+        (proj, points)
+    }
+
+    #[test]
+    fn kalman_reduces_noise() {
+        let (proj, mut points) = noisy_track(30);
+        let error = |points: &[GpsPoint]| -> f64 {
+            points
+                .iter()
+                .map(|pt| proj.project(pt.x, pt.y).1.abs())
+                .sum::<f64>()
+                / points.len() as f64
+        };
+        let raw_error = error(&points);
+        smooth(&mut points, Smoothing::Kalman);
+        assert!(error(&points) < raw_error / 2.0);
+        // Progress along the track is preserved
+        let (x_end, _) = proj.project(points[29].x, points[29].y);
+        assert!((x_end - 29.0 * 50.0).abs() < 10.0);
+    }
+
+    #[test]
+    fn short_tracks_unchanged() {
+        let (_, mut points) = noisy_track(2);
+        let orig = points.clone();
+        smooth(&mut points, Smoothing::Kalman);
+        for (pt, orig) in points.iter().zip(&orig) {
+            assert_eq!((pt.x, pt.y), (orig.x, orig.y));
+        }
+    }
+}
@@ -0,0 +1,129 @@
+//! Geohash based spatial index of logged fixes.
+//!
+//! Every fix stores its geohash, so bounding box queries can be answered with a few
+//! prefix range scans on an ordinary B-tree index, both in SQLite and PostgreSQL.
+
+use geo_types::Coord;
+
+/// Length of the stored geohash (cells of about 5 x 5 meters)
+pub const GEOHASH_PRECISION: usize = 9;
+/// Maximal number of prefix ranges used for covering a bounding box
+const MAX_COVER_CELLS: usize = 16;
+/// Geohash alphabet in sort order
+const BASE32: &[u8] = b"0123456789bcdefghjkmnpqrstuvwxyz";
+
+/// Geohash of a position
+pub fn encode(x: f64, y: f64) -> Option<String> {
+    geohash::encode(Coord { x, y }, GEOHASH_PRECISION).ok()
+}
+
+/// Cell size (width, height) in degrees of geohashes with the given length
+fn cell_size(len: usize) -> (f64, f64) {
+    let bits = 5 * len as i32;
+    let lon_bits = (bits + 1) / 2;
+    let lat_bits = bits / 2;
+    (360.0 / 2f64.powi(lon_bits), 180.0 / 2f64.powi(lat_bits))
+}
+
+/// Geohash ranges `[from, to)` covering a bounding box `[xmin, ymin, xmax, ymax]`.
+///
+/// Uses the longest prefix length needing at most `MAX_COVER_CELLS` cells.
+/// Returns `None` if the whole world has to be scanned.
+pub fn cover(bbox: [f64; 4]) -> Option<Vec<(String, Option<String>)>> {
+    let [xmin, ymin, xmax, ymax] = bbox;
+    let index = |val: f64, min: f64, size: f64| ((val - min) / size).floor() as i64;
+    let mut best = None;
+    for len in 1..=GEOHASH_PRECISION {
+        let (w, h) = cell_size(len);
+        let (ix0, ix1) = (
+            index(xmin, -180.0, w),
+            index(xmax.min(180.0 - w / 2.0), -180.0, w),
+        );
+        let (iy0, iy1) = (
+            index(ymin, -90.0, h),
+            index(ymax.min(90.0 - h / 2.0), -90.0, h),
+        );
+        let count = (ix1 - ix0 + 1).max(0) as usize * (iy1 - iy0 + 1).max(0) as usize;
+        if count > MAX_COVER_CELLS {
+            break;
+        }
+        best = Some((len, ix0..=ix1, iy0..=iy1));
+    }
+    let (len, xs, ys) = best?;
+    let (w, h) = cell_size(len);
+    let mut prefixes = Vec::new();
+    for iy in ys {
+        for ix in xs.clone() {
+            let center = Coord {
+                x: -180.0 + (ix as f64 + 0.5) * w,
+                y: -90.0 + (iy as f64 + 0.5) * h,
+            };
+            if let Ok(prefix) = geohash::encode(center, len) {
+                prefixes.push(prefix);
+            }
+        }
+    }
+    prefixes.sort();
+    // Merge adjacent prefixes into a single range
+    let mut ranges: Vec<(String, Option<String>)> = Vec::new();
+    for prefix in prefixes {
+        let end = next_prefix(&prefix);
+        match ranges.last_mut() {
+            Some(last) if last.1.as_ref() == Some(&prefix) => last.1 = end,
+            _ => ranges.push((prefix, end)),
+        }
+    }
+    Some(ranges)
+}
+
+/// Smallest geohash greater than all geohashes starting with `prefix`.
+///
+/// Returns `None` for the prefix of the last cell.
+fn next_prefix(prefix: &str) -> Option<String> {
+    let mut chars = prefix.as_bytes().to_vec();
+    while let Some(c) = chars.pop() {
+        if let Some(pos) = BASE32.iter().position(|b| *b == c) {
+            if pos + 1 < BASE32.len() {
+                chars.push(BASE32[pos + 1]);
+                return String::from_utf8(chars).ok();
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn next_prefix_increments() {
+        assert_eq!(next_prefix("u0").as_deref(), Some("u1"));
+        assert_eq!(next_prefix("u9").as_deref(), Some("ub"));
+        assert_eq!(next_prefix("uz").as_deref(), Some("v"));
+        assert_eq!(next_prefix("zz"), None);
+    }
+
+    #[test]
+    fn cover_contains_positions() {
+        let bbox = [9.42, 47.04, 9.45, 47.06];
+        let ranges = cover(bbox).unwrap();
+        assert!(!ranges.is_empty());
+        assert!(ranges.len() <= MAX_COVER_CELLS);
+        for (x, y) in [(9.42, 47.04), (9.435, 47.05), (9.45, 47.06)] {
+            let hash = encode(x, y).unwrap();
+            assert!(ranges
+                .iter()
+                .any(|(from, to)| hash >= *from && to.as_ref().is_none_or(|to| hash < *to)));
+        }
+        let outside = encode(9.5, 47.05).unwrap();
+        assert!(!ranges
+            .iter()
+            .any(|(from, to)| outside >= *from && to.as_ref().is_none_or(|to| outside < *to)));
+    }
+
+    #[test]
+    fn cover_world() {
+        assert!(cover([-180.0, -90.0, 180.0, 90.0]).is_none());
+    }
+}
@@ -0,0 +1,232 @@
+use crate::db::GpsPoint;
+use crate::stats::{DistanceStats, ElevationDiffStats};
+use serde::{Deserialize, Serialize};
+
+const METERS_PER_MILE: f64 = 1609.344;
+/// Minimal custom split distance (meters)
+const MIN_SPLIT_DISTANCE: f64 = 10.0;
+
+/// Split distance unit
+#[derive(Deserialize, Clone, Copy, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum SplitUnit {
+    Km,
+    Mi,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct SplitParams {
+    /// Split distance unit (default: km)
+    pub split_unit: Option<SplitUnit>,
+    /// Custom split distance in meters (min. 10)
+    pub split_distance: Option<f64>,
+}
+
+impl SplitParams {
+    /// Split distance in meters
+    pub fn distance(&self) -> f64 {
+        match (self.split_distance, self.split_unit) {
+            (Some(distance), _) if distance > 0.0 => distance.max(MIN_SPLIT_DISTANCE),
+            (_, Some(SplitUnit::Mi)) => METERS_PER_MILE,
+            _ => 1000.0,
+        }
+    }
+}
+
+/// Summary of a track section
+#[derive(Serialize, Debug)]
+pub struct Split {
+    /// Sequence number starting with 1
+    pub no: usize,
+    /// Lap name from `tag` annotation
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Distance from start of track (meters)
+    pub start: f64,
+    /// Length (meters)
+    pub distance: f64,
+    /// Duration (seconds)
+    pub duration: Option<f64>,
+    /// Pace (minutes per km)
+    pub pace: Option<f64>,
+    /// Average speed (km/h)
+    pub speed: Option<f64>,
+    pub elevation_up: i64,
+    pub elevation_down: i64,
+}
+
+/// Track points with distance from start and time
+struct Section<'a> {
+    points: &'a [&'a GpsPoint],
+    distances: &'a [f64],
+    epochs: Vec<Option<i64>>,
+}
+
+impl Section<'_> {
+    /// Time at distance from start with linear interpolation
+    fn time_at(&self, distance: f64) -> Option<f64> {
+        let i = self
+            .distances
+            .partition_point(|d| *d < distance)
+            .min(self.distances.len() - 1);
+        let t1 = self.epochs[i]? as f64;
+        if i == 0 || self.distances[i] <= distance {
+            return Some(t1);
+        }
+        let t0 = self.epochs[i - 1]? as f64;
+        let (d0, d1) = (self.distances[i - 1], self.distances[i]);
+        Some(t0 + (t1 - t0) * (distance - d0) / (d1 - d0))
+    }
+
+    /// Summarize section between two distances from start
+    fn summary(&self, no: usize, name: Option<String>, d0: f64, d1: f64) -> Split {
+        let duration = self
+            .time_at(d0)
+            .zip(self.time_at(d1))
+            .map(|(t0, t1)| t1 - t0);
+        let distance = d1 - d0;
+        // Points within section including the last point before
+        let first = self
+            .distances
+            .partition_point(|d| *d <= d0)
+            .saturating_sub(1);
+        let last = self.distances.partition_point(|d| *d <= d1);
+        let elevation = ElevationDiffStats::from_points(self.points[first..last].iter().copied());
+        Split {
+            no,
+            name,
+            start: d0,
+            distance,
+            duration,
+            pace: duration
+                .filter(|_| distance > 0.0)
+                .map(|duration| duration / 60.0 / (distance / 1000.0)),
+            speed: duration
+                .filter(|duration| *duration > 0.0)
+                .map(|duration| distance / duration * 3.6),
+            elevation_up: elevation.elevation_up(),
+            elevation_down: elevation.elevation_down(),
+        }
+    }
+}
+
+/// Split track into sections of equal distance
+pub fn splits(points: &[&GpsPoint], split_distance: f64) -> Vec<Split> {
+    let distance_stats = DistanceStats::from_xy_iter(points.iter().map(|pt| (pt.x, pt.y)));
+    let section = Section {
+        points,
+        distances: distance_stats.cumulative(),
+        epochs: points.iter().map(|pt| pt.epoch()).collect(),
+    };
+    let total = *section.distances.last().unwrap_or(&0.0);
+    let count = (total / split_distance).ceil() as usize;
+    (0..count)
+        .map(|i| {
+            let d0 = i as f64 * split_distance;
+            let d1 = (d0 + split_distance).min(total);
+            section.summary(i + 1, None, d0, d1)
+        })
+        .collect()
+}
+
+/// Split track into laps started by points with `tag` annotation
+pub fn laps(points: &[&GpsPoint]) -> Vec<Split> {
+    let markers: Vec<(usize, String)> = points
+        .iter()
+        .enumerate()
+        .filter_map(|(i, pt)| {
+            serde_json::from_str::<serde_json::Map<String, serde_json::Value>>(&pt.annotations)
+                .ok()?
+                .get("tag")?
+                .as_str()
+                .map(|tag| (i, tag.to_string()))
+        })
+        .collect();
+    if markers.is_empty() {
+        return Vec::new();
+    }
+    let distance_stats = DistanceStats::from_xy_iter(points.iter().map(|pt| (pt.x, pt.y)));
+    let section = Section {
+        points,
+        distances: distance_stats.cumulative(),
+        epochs: points.iter().map(|pt| pt.epoch()).collect(),
+    };
+    // Lap before first marker
+    let mut starts = Vec::new();
+    if markers[0].0 > 0 {
+        starts.push((0, None));
+    }
+    starts.extend(markers.into_iter().map(|(i, tag)| (i, Some(tag))));
+    let last = points.len() - 1;
+    starts
+        .iter()
+        .enumerate()
+        .filter_map(|(i, (start, name))| {
+            let end = starts.get(i + 1).map(|(end, _)| *end).unwrap_or(last);
+            (*start < end).then_some((*start, end, name))
+        })
+        .enumerate()
+        .map(|(i, (start, end, name))| {
+            section.summary(
+                i + 1,
+                name.clone(),
+                section.distances[start],
+                section.distances[end],
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn track() -> Vec<GpsPoint> {
+        // 0.009° at the equator is about 1 km
+        (0..=5)
+            .map(|i| GpsPoint {
+                x: i as f64 * 0.0045,
+                ts: format!("2025-10-09 08:{:02}:00", i * 3),
+                annotations: if i == 2 {
+                    r#"{"tag":"uphill"}"#.to_string()
+                } else {
+                    "{}".to_string()
+                },
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn split_distance() {
+        let params = |split_distance| SplitParams {
+            split_unit: None,
+            split_distance,
+        };
+        assert_eq!(params(None).distance(), 1000.0);
+        assert_eq!(params(Some(0.001)).distance(), MIN_SPLIT_DISTANCE);
+        assert_eq!(params(Some(-5.0)).distance(), 1000.0);
+    }
+
+    #[test]
+    fn km_splits() {
+        let points = track();
+        let points: Vec<&GpsPoint> = points.iter().collect();
+        let splits = splits(&points, 1000.0);
+        assert_eq!(splits.len(), 3);
+        assert!((splits[0].duration.unwrap() - 360.0).abs() < 1.0);
+        assert!((splits[0].pace.unwrap() - 6.0).abs() < 0.05);
+        assert!(splits[2].distance < 510.0);
+    }
+
+    #[test]
+    fn tagged_laps() {
+        let points = track();
+        let points: Vec<&GpsPoint> = points.iter().collect();
+        let laps = laps(&points);
+        assert_eq!(laps.len(), 2);
+        assert_eq!(laps[0].name, None);
+        assert_eq!(laps[1].name.as_deref(), Some("uphill"));
+        assert_eq!(laps[1].duration, Some(540.0));
+    }
+}
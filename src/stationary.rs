@@ -0,0 +1,305 @@
+use crate::db::GpsPoint;
+use chrono::DateTime;
+use geo::{Distance, Haversine, Point};
+use serde_json::Value;
+
+/// Parameters for detecting stationary clusters
+#[derive(Clone, Debug)]
+pub struct StationaryConfig {
+    /// Maximal distance of a point from the cluster center (meters)
+    pub radius: f64,
+    /// Minimal duration of a cluster (seconds)
+    pub min_duration: i64,
+    /// Maximal reported velocity of a stationary point (km/h)
+    pub max_speed: i16,
+}
+
+impl StationaryConfig {
+    pub fn from_env() -> Self {
+        StationaryConfig {
+            radius: dotenvy::var("STATIONARY_RADIUS")
+                .unwrap_or("50".to_string())
+                .parse()
+                .unwrap_or(50.0),
+            min_duration: dotenvy::var("STATIONARY_MIN_DURATION")
+                .unwrap_or("300".to_string())
+                .parse()
+                .unwrap_or(300),
+            max_speed: dotenvy::var("STATIONARY_MAX_SPEED")
+                .unwrap_or("2".to_string())
+                .parse()
+                .unwrap_or(2),
+        }
+    }
+
+    /// Configuration for collapsing stationary fixes at ingestion time, if enabled
+    /// with `COLLAPSE_STATIONARY=true`
+    pub fn ingestion_from_env() -> Option<Self> {
+        dotenvy::var("COLLAPSE_STATIONARY")
+            .map(|s| s == "true")
+            .unwrap_or(false)
+            .then(Self::from_env)
+    }
+
+    /// Check whether a reported velocity excludes a stationary point.
+    ///
+    /// Without reported velocity only the distance to the cluster center decides.
+    pub fn is_moving(&self, speed: Option<i16>) -> bool {
+        speed.is_some_and(|speed| speed > self.max_speed)
+    }
+}
+
+/// Collapse stationary clusters into a single point with duration.
+///
+/// The representative point is located at the cluster centroid and carries the
+/// annotations `ts_end`, `duration` (seconds) and `points` (number of collapsed points).
+pub fn collapse(points: Vec<GpsPoint>, cfg: &StationaryConfig) -> Vec<GpsPoint> {
+    let mut collapsed = Vec::with_capacity(points.len());
+    let mut i = 0;
+    while i < points.len() {
+        let (mut sum_x, mut sum_y) = (points[i].x, points[i].y);
+        let mut j = i + 1;
+        while j < points.len() && !cfg.is_moving(points[j].speed) {
+            let count = (j - i) as f64;
+            let center = Point::new(sum_x / count, sum_y / count);
+            if Haversine::distance(center, Point::new(points[j].x, points[j].y)) > cfg.radius {
+                break;
+            }
+            sum_x += points[j].x;
+            sum_y += points[j].y;
+            j += 1;
+        }
+        let duration = match (points[i].epoch(), points[j - 1].epoch()) {
+            (Some(t0), Some(t1)) => t1 - t0,
+            _ => 0,
+        };
+        if j - i > 1 && duration >= cfg.min_duration {
+            let count = (j - i) as f64;
+            let mut pt = points[i].clone();
+            pt.x = sum_x / count;
+            pt.y = sum_y / count;
+            pt.accuracy = points[i..j].iter().filter_map(|pt| pt.accuracy).min();
+            set_stay_annotations(&mut pt, &points[j - 1].ts, duration, j - i);
+            collapsed.push(pt);
+            i = j;
+        } else {
+            collapsed.push(points[i].clone());
+            i += 1;
+        }
+    }
+    collapsed
+}
+
+/// Add stay information to JSON annotations of a point representing a stay
+fn set_stay_annotations(pt: &mut GpsPoint, ts_end: &str, duration: i64, count: usize) {
+    pt.annotations = stay_annotations(&pt.annotations, ts_end, duration, count);
+}
+
+/// JSON annotations with stay information
+fn stay_annotations(annotations: &str, ts_end: &str, duration: i64, count: usize) -> String {
+    let mut json: serde_json::Map<String, Value> =
+        serde_json::from_str(annotations).unwrap_or_default();
+    json.insert("ts_end".to_string(), Value::from(ts_end));
+    json.insert("duration".to_string(), Value::from(duration));
+    json.insert("points".to_string(), Value::from(count));
+    Value::Object(json).to_string()
+}
+
+/// Stay of a device collected at ingestion time.
+///
+/// Fixes are logged as usual until the stay lasts `min_duration`. Then the logged
+/// fixes are merged into the first fix of the stay, which is updated with every
+/// further stationary fix.
+#[derive(Clone, Debug)]
+pub struct PendingStay {
+    /// Id of the first logged fix of the stay
+    pub anchor_id: i64,
+    /// Ids of further logged fixes, deleted when merging
+    pub merged_ids: Vec<i64>,
+    /// Annotations of the first fix
+    annotations: String,
+    start_ts: i64,
+    end_ts: i64,
+    sum_x: f64,
+    sum_y: f64,
+    count: usize,
+    accuracy: Option<i32>,
+}
+
+impl PendingStay {
+    /// Start a stay with a logged fix, if its reported velocity is stationary
+    pub fn start(
+        anchor_id: i64,
+        (x, y): (f64, f64),
+        ts: i64,
+        speed: Option<i16>,
+        accuracy: Option<i32>,
+        annotations: &str,
+        cfg: &StationaryConfig,
+    ) -> Option<Self> {
+        is_stationary_fix(speed, cfg).then(|| PendingStay {
+            anchor_id,
+            merged_ids: Vec::new(),
+            annotations: annotations.to_string(),
+            start_ts: ts,
+            end_ts: ts,
+            sum_x: x,
+            sum_y: y,
+            count: 1,
+            accuracy,
+        })
+    }
+
+    /// Add a fix to the stay, returns `false` if the device moved
+    pub fn add(
+        &mut self,
+        (x, y): (f64, f64),
+        ts: i64,
+        speed: Option<i16>,
+        accuracy: Option<i32>,
+        cfg: &StationaryConfig,
+    ) -> bool {
+        let same_day = match (
+            DateTime::from_timestamp(self.start_ts, 0),
+            DateTime::from_timestamp(ts, 0),
+        ) {
+            (Some(start), Some(end)) => start.date_naive() == end.date_naive(),
+            _ => false,
+        };
+        let (cx, cy) = self.center();
+        if !is_stationary_fix(speed, cfg)
+            || !same_day
+            || ts < self.end_ts
+            || Haversine::distance(Point::new(cx, cy), Point::new(x, y)) > cfg.radius
+        {
+            return false;
+        }
+        self.end_ts = ts;
+        self.sum_x += x;
+        self.sum_y += y;
+        self.count += 1;
+        self.accuracy = self.accuracy.into_iter().chain(accuracy).min();
+        true
+    }
+
+    /// Stay lasts long enough for collapsing
+    pub fn is_complete(&self, cfg: &StationaryConfig) -> bool {
+        self.duration() >= cfg.min_duration
+    }
+
+    pub fn duration(&self) -> i64 {
+        self.end_ts - self.start_ts
+    }
+
+    /// Centroid of the stay
+    pub fn center(&self) -> (f64, f64) {
+        (
+            self.sum_x / self.count as f64,
+            self.sum_y / self.count as f64,
+        )
+    }
+
+    pub fn accuracy(&self) -> Option<i32> {
+        self.accuracy
+    }
+
+    /// Annotations of the first fix with stay information
+    pub fn annotations(&self) -> String {
+        let ts_end = DateTime::from_timestamp(self.end_ts, 0)
+            .unwrap_or_default()
+            .format("%F %T")
+            .to_string();
+        stay_annotations(&self.annotations, &ts_end, self.duration(), self.count)
+    }
+}
+
+/// Fixes are only collapsed at ingestion time with a reported stationary velocity
+fn is_stationary_fix(speed: Option<i16>, cfg: &StationaryConfig) -> bool {
+    speed.is_some_and(|speed| speed <= cfg.max_speed)
+}
+
+/// Duration of a stay in seconds, 0 for points which are no stays
+pub fn stay_duration(annotations: &str) -> i64 {
+    serde_json::from_str::<serde_json::Map<String, Value>>(annotations)
+        .ok()
+        .and_then(|json| json.get("duration").and_then(|v| v.as_i64()))
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(x: f64, y: f64, ts: &str, speed: Option<i16>) -> GpsPoint {
+        GpsPoint {
+            x,
+            y,
+            ts: ts.to_string(),
+            speed,
+            ..Default::default()
+        }
+    }
+
+    fn config() -> StationaryConfig {
+        StationaryConfig {
+            radius: 50.0,
+            min_duration: 300,
+            max_speed: 2,
+        }
+    }
+
+    #[test]
+    fn collapse_stay() {
+        let points = vec![
+            point(9.4300, 47.05, "2025-10-09 08:00:00", Some(20)),
+            point(9.4400, 47.05, "2025-10-09 08:01:00", None),
+            point(9.4401, 47.05, "2025-10-09 08:04:00", None),
+            point(9.4400, 47.05, "2025-10-09 08:07:00", Some(0)),
+            point(9.4500, 47.05, "2025-10-09 08:08:00", Some(20)),
+        ];
+        let collapsed = collapse(points, &config());
+        assert_eq!(collapsed.len(), 3);
+        assert_eq!(stay_duration(&collapsed[1].annotations), 360);
+        assert_eq!(collapsed[1].ts, "2025-10-09 08:01:00");
+        assert_eq!(stay_duration(&collapsed[2].annotations), 0);
+    }
+
+    #[test]
+    fn keep_short_stays_and_moving_points() {
+        let points = vec![
+            point(9.4400, 47.05, "2025-10-09 08:00:00", None),
+            point(9.4400, 47.05, "2025-10-09 08:02:00", None),
+            point(9.4400, 47.05, "2025-10-09 08:04:00", Some(10)),
+            point(9.4400, 47.05, "2025-10-09 08:20:00", Some(10)),
+        ];
+        let collapsed = collapse(points, &config());
+        assert_eq!(collapsed.len(), 4);
+        assert!(collapsed
+            .iter()
+            .all(|pt| stay_duration(&pt.annotations) == 0));
+    }
+
+    #[test]
+    fn pending_stay() {
+        let cfg = config();
+        // 2025-10-09 08:00:00 UTC
+        let ts = 1759996800;
+        assert!(PendingStay::start(1, (9.44, 47.05), ts, None, None, "{}", &cfg).is_none());
+        let mut stay =
+            PendingStay::start(1, (9.44, 47.05), ts, Some(0), Some(20), "{}", &cfg).unwrap();
+        assert!(stay.add((9.4401, 47.05), ts + 120, Some(1), Some(10), &cfg));
+        assert!(!stay.is_complete(&cfg));
+        assert!(!stay.add((9.44, 47.05), ts + 240, Some(10), None, &cfg));
+        assert!(!stay.add((9.45, 47.05), ts + 240, Some(0), None, &cfg));
+        assert!(stay.add((9.44, 47.05), ts + 300, Some(0), None, &cfg));
+        assert!(stay.is_complete(&cfg));
+        assert_eq!(stay.accuracy(), Some(10));
+        assert_eq!(stay_duration(&stay.annotations()), 300);
+        assert!(stay
+            .annotations()
+            .contains("\"ts_end\":\"2025-10-09 08:05:00\""));
+        assert!(stay.annotations().contains("\"points\":3"));
+        // Stays end at midnight
+        assert!(!stay.add((9.44, 47.05), ts + 16 * 3600, Some(0), None, &cfg));
+    }
+}
@@ -1,9 +1,13 @@
 use crate::db::GpsPoint;
-use chrono::{DateTime, FixedOffset, Local, NaiveDateTime};
+use chrono::DateTime;
 use geo::algorithm::vincenty_distance::VincentyDistance;
+use geo::{Distance, Haversine};
 use geojson::{JsonObject, JsonValue};
 use stats::{MinMax, OnlineStats};
 
+/// Minimal speed for counting a segment as moving (km/h)
+const MOVING_SPEED_THRESHOLD: f64 = 2.0;
+
 #[derive(Default)]
 pub struct TrackStats {
     ts: MinMax<i64>,
@@ -11,25 +15,49 @@ pub struct TrackStats {
     speed_stats: OnlineStats,
     elevation: MinMax<i16>,
     elevation_stats: OnlineStats,
+    /// Time spent moving (seconds)
+    moving_time: i64,
+    /// Time spent without moving (seconds)
+    stopped_time: i64,
+    /// Distance covered while moving (meters)
+    moving_distance: f64,
 }
 
 impl TrackStats {
     pub fn from_iter<'a>(iter: impl Iterator<Item = &'a GpsPoint>) -> Self {
         let mut stats = Self::default();
-        let tz = *Local::now().offset();
+        let mut prev: Option<(i64, geo::Point)> = None;
         for pt in iter {
-            // SQLite stores in UTC without TZ
-            let dt = DateTime::<FixedOffset>::parse_from_str(&pt.ts, "%F %T%#z")
-                .or(NaiveDateTime::parse_from_str(&pt.ts, "%F %T")
-                    .map(|utcts| utcts.and_local_timezone(tz).unwrap()));
-            // log::debug!("Timestamp `{}` -> `{dt:?}`", &pt.ts);
-            match dt {
-                Ok(dt) => stats.ts.add(dt.timestamp()),
-                Err(e) => {
-                    log::info!("Ignoring invalid timestamp `{}`: {e}", &pt.ts);
+            let ts = pt.epoch();
+            match ts {
+                Some(ts) => stats.ts.add(ts),
+                None => log::info!("Ignoring invalid timestamp `{}`", &pt.ts),
+            }
+            // Segment from previous point
+            let pos = geo::Point::new(pt.x, pt.y);
+            let mut segment_speed = None;
+            if let (Some((prev_ts, prev_pos)), Some(ts)) = (prev, ts) {
+                let dt = ts - prev_ts;
+                if dt > 0 {
+                    let distance = Haversine::distance(prev_pos, pos);
+                    let speed = pt
+                        .speed
+                        .map(f64::from)
+                        .unwrap_or(distance / dt as f64 * 3.6);
+                    if speed >= MOVING_SPEED_THRESHOLD {
+                        stats.moving_time += dt;
+                        stats.moving_distance += distance;
+                    } else {
+                        stats.stopped_time += dt;
+                    }
+                    segment_speed = Some(speed);
                 }
             }
-            if let Some(speed) = pt.speed {
+            if let Some(ts) = ts {
+                prev = Some((ts, pos));
+            }
+            // Derive speed from distance and time, if not reported
+            if let Some(speed) = pt.speed.or(segment_speed.map(|speed| speed.round() as i16)) {
                 stats.speed.add(speed);
                 stats.speed_stats.add(speed);
             }
@@ -54,6 +82,11 @@ impl TrackStats {
         } else {
             None
         };
+        let moving_speed =
+            (self.moving_time > 0).then(|| self.moving_distance / self.moving_time as f64 * 3.6);
+        // Pace in minutes per km
+        let pace = (self.moving_distance > 0.0)
+            .then(|| self.moving_time as f64 / 60.0 / (self.moving_distance / 1000.0));
 
         JsonObject::from_iter([
             (
@@ -92,6 +125,13 @@ impl TrackStats {
                 "duration".to_string(),
                 JsonValue::from(duration.map(|v| v.num_seconds())),
             ),
+            ("moving_time".to_string(), JsonValue::from(self.moving_time)),
+            (
+                "stopped_time".to_string(),
+                JsonValue::from(self.stopped_time),
+            ),
+            ("moving_speed".to_string(), JsonValue::from(moving_speed)),
+            ("pace".to_string(), JsonValue::from(pace)),
         ])
     }
 }
@@ -125,45 +165,157 @@ impl BboxStats {
 #[derive(Default)]
 pub struct DistanceStats {
     distance: f64,
+    /// Distance from start for each point
+    cumulative: Vec<f64>,
 }
 
 impl DistanceStats {
     pub fn from_xy_iter(iter: impl Iterator<Item = (f64, f64)>) -> Self {
         let mut stats = Self::default();
         let points = iter.collect::<Vec<_>>();
+        if !points.is_empty() {
+            stats.cumulative.push(0.0);
+        }
         points.windows(2).for_each(|pair| {
             let p1 = geo::Point::from(pair[0]);
             let p2 = geo::Point::from(pair[1]);
             let distance = p1.vincenty_distance(&p2).unwrap();
             stats.distance += distance;
+            stats.cumulative.push(stats.distance);
         });
         stats
     }
+    /// Total distance computed elsewhere, e.g. by the database
+    pub fn from_distance(distance: f64) -> Self {
+        Self {
+            distance,
+            ..Default::default()
+        }
+    }
+    /// Distance from start for each point in meters
+    pub fn cumulative(&self) -> &[f64] {
+        &self.cumulative
+    }
     pub fn as_properties(&self) -> JsonObject {
         JsonObject::from_iter([("distance".to_string(), JsonValue::from(self.distance))])
     }
 }
 
+/// Minimal elevation change counted from GPS altitudes (meters)
+const GPS_ELEVATION_THRESHOLD: f64 = 5.0;
+/// Minimal elevation change counted from barometric altitudes (meters)
+const BARO_ELEVATION_THRESHOLD: f64 = 1.0;
+/// Minimal elevation change counted from elevation model lookups (meters)
+const DEM_ELEVATION_THRESHOLD: f64 = 2.0;
+/// Vertical accuracy assumed for GPS altitudes without `v_accuracy` (meters)
+const DEFAULT_V_ACCURACY: f64 = 10.0;
+/// Vertical accuracy assumed for elevation model lookups (meters)
+const DEM_V_ACCURACY: f64 = 5.0;
+/// Number of neighbours on each side used for smoothing altitudes
+const ELEVATION_SMOOTHING_WINDOW: usize = 2;
+/// Minimal share of points with pressure or elevation model lookup for using them
+const MIN_SOURCE_SHARE: f64 = 0.8;
+
+#[derive(Default, Clone, Copy)]
+enum ElevationSource {
+    #[default]
+    Gps,
+    Barometric,
+    Dem,
+}
+
 #[derive(Default)]
 pub struct ElevationDiffStats {
     elevation_up: i64,
     elevation_down: i64,
+    source: ElevationSource,
 }
 
 impl ElevationDiffStats {
-    pub fn from_iter(iter: impl Iterator<Item = i16>) -> Self {
-        let mut stats = Self::default();
+    /// Calculate elevation gain and loss.
+    ///
+    /// Uses elevation model lookups or barometric pressure (`p` annotation) when
+    /// available for most points, otherwise GPS altitudes weighted by vertical accuracy.
+    /// Altitudes are smoothed and only changes above a threshold are counted (hysteresis).
+    pub fn from_points<'a>(iter: impl Iterator<Item = &'a GpsPoint>) -> Self {
         let points = iter.collect::<Vec<_>>();
-        points.windows(2).for_each(|pair| {
-            let (elev0, elev1) = (pair[0], pair[1]);
-            if elev1 > elev0 {
-                stats.elevation_up += (elev1 - elev0) as i64;
-            } else {
-                stats.elevation_down += (elev0 - elev1) as i64;
-            }
+        let min_count = points.len() as f64 * MIN_SOURCE_SHARE;
+        let dem_altitudes = points
+            .iter()
+            .filter(|pt| pt.dem_elevation)
+            .filter_map(|pt| pt.elevation.map(|elev| (f64::from(elev), 1.0)))
+            .collect::<Vec<_>>();
+        let pressure_altitudes = points
+            .iter()
+            .filter_map(|pt| pressure(pt).map(|p| (barometric_altitude(p), 1.0)))
+            .collect::<Vec<_>>();
+        let (source, altitudes, threshold) = if points.is_empty() {
+            (ElevationSource::Gps, Vec::new(), GPS_ELEVATION_THRESHOLD)
+        } else if dem_altitudes.len() as f64 >= min_count {
+            (ElevationSource::Dem, dem_altitudes, DEM_ELEVATION_THRESHOLD)
+        } else if pressure_altitudes.len() as f64 >= min_count {
+            (
+                ElevationSource::Barometric,
+                pressure_altitudes,
+                BARO_ELEVATION_THRESHOLD,
+            )
+        } else {
+            let altitudes = points
+                .iter()
+                .filter_map(|pt| {
+                    let v_accuracy = if pt.dem_elevation {
+                        DEM_V_ACCURACY
+                    } else {
+                        pt.v_accuracy
+                            .filter(|vac| *vac > 0)
+                            .map(f64::from)
+                            .unwrap_or(DEFAULT_V_ACCURACY)
+                    };
+                    pt.elevation
+                        .map(|elev| (f64::from(elev), 1.0 / v_accuracy.powi(2)))
+                })
+                .collect::<Vec<_>>();
+            (ElevationSource::Gps, altitudes, GPS_ELEVATION_THRESHOLD)
+        };
+        let mut stats = Self::from_weighted_altitudes(&altitudes, threshold);
+        stats.source = source;
+        stats
+    }
+    fn from_weighted_altitudes(altitudes: &[(f64, f64)], threshold: f64) -> Self {
+        let mut stats = Self::default();
+        // Weighted moving average
+        let smoothed = (0..altitudes.len()).map(|i| {
+            let window = &altitudes[i.saturating_sub(ELEVATION_SMOOTHING_WINDOW)
+                ..(i + ELEVATION_SMOOTHING_WINDOW + 1).min(altitudes.len())];
+            let weights: f64 = window.iter().map(|(_, w)| w).sum();
+            window.iter().map(|(alt, w)| alt * w).sum::<f64>() / weights
         });
+        let (mut up, mut down) = (0.0, 0.0);
+        let mut reference: Option<f64> = None;
+        for alt in smoothed {
+            match reference {
+                None => reference = Some(alt),
+                Some(ref_alt) if alt - ref_alt >= threshold => {
+                    up += alt - ref_alt;
+                    reference = Some(alt);
+                }
+                Some(ref_alt) if ref_alt - alt >= threshold => {
+                    down += ref_alt - alt;
+                    reference = Some(alt);
+                }
+                _ => {}
+            }
+        }
+        stats.elevation_up = up.round() as i64;
+        stats.elevation_down = down.round() as i64;
         stats
     }
+    pub fn elevation_up(&self) -> i64 {
+        self.elevation_up
+    }
+    pub fn elevation_down(&self) -> i64 {
+        self.elevation_down
+    }
     pub fn as_properties(&self) -> JsonObject {
         JsonObject::from_iter([
             (
@@ -174,6 +326,135 @@ impl ElevationDiffStats {
                 "elevation_down".to_string(),
                 JsonValue::from(self.elevation_down),
             ),
+            (
+                "elevation_source".to_string(),
+                JsonValue::from(match self.source {
+                    ElevationSource::Gps => "gps",
+                    ElevationSource::Barometric => "barometric",
+                    ElevationSource::Dem => "dem",
+                }),
+            ),
         ])
     }
 }
+
+/// Barometric pressure from `p` annotation (kPa)
+fn pressure(pt: &GpsPoint) -> Option<f64> {
+    serde_json::from_str::<JsonObject>(&pt.annotations)
+        .ok()
+        .and_then(|annotations| annotations.get("p").and_then(JsonValue::as_f64))
+        .filter(|p| *p > 0.0)
+}
+
+/// Altitude from barometric pressure in kPa (international standard atmosphere)
+fn barometric_altitude(pressure: f64) -> f64 {
+    44330.0 * (1.0 - (pressure / 101.325).powf(1.0 / 5.255))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(x: f64, ts: &str) -> GpsPoint {
+        GpsPoint {
+            x,
+            y: 0.0,
+            ts: ts.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn moving_and_stopped_time() {
+        // 0.009° at the equator is about 1 km
+        let points = [
+            point(0.0, "2025-10-09 08:00:00"),
+            point(0.009, "2025-10-09 08:05:00"),
+            point(0.009, "2025-10-09 08:15:00"),
+            point(0.018, "2025-10-09 08:20:00+00"),
+        ];
+        let stats = TrackStats::from_iter(points.iter()).as_properties();
+        assert_eq!(stats["ts_start"], "2025-10-09 08:00:00+0000");
+        assert_eq!(stats["duration"], 1200);
+        assert_eq!(stats["moving_time"], 600);
+        assert_eq!(stats["stopped_time"], 600);
+        let pace = stats["pace"].as_f64().unwrap();
+        assert!((pace - 5.0).abs() < 0.01);
+    }
+
+    fn elevation_point(elevation: i16, v_accuracy: Option<i16>) -> GpsPoint {
+        GpsPoint {
+            elevation: Some(elevation),
+            v_accuracy,
+            ..Default::default()
+        }
+    }
+
+    fn elevation_stats(points: &[GpsPoint]) -> JsonObject {
+        ElevationDiffStats::from_points(points.iter()).as_properties()
+    }
+
+    #[test]
+    fn elevation_jitter_below_threshold() {
+        let points: Vec<_> = (0..20)
+            .map(|i| elevation_point(500 + (i % 2) * 4, Some(5)))
+            .collect();
+        let stats = elevation_stats(&points);
+        assert_eq!(stats["elevation_source"], "gps");
+        assert_eq!(stats["elevation_up"], 0);
+        assert_eq!(stats["elevation_down"], 0);
+    }
+
+    #[test]
+    fn elevation_prefers_barometric_pressure() {
+        // GPS altitude climbs 100 m, pressure decreases by about 50 m
+        let points: Vec<_> = (0..20)
+            .map(|i| GpsPoint {
+                annotations: format!(r#"{{"p":{}}}"#, 95.0 - f64::from(i) * 0.03),
+                ..elevation_point(500 + i * 5, Some(5))
+            })
+            .collect();
+        let stats = elevation_stats(&points);
+        assert_eq!(stats["elevation_source"], "barometric");
+        let up = stats["elevation_up"].as_i64().unwrap();
+        assert!((40..60).contains(&up), "{up}");
+        assert_eq!(stats["elevation_down"], 0);
+    }
+
+    #[test]
+    fn elevation_source_selection() {
+        let dem_point = |i: i16| GpsPoint {
+            dem_elevation: true,
+            annotations: r#"{"p":95.0}"#.to_string(),
+            ..elevation_point(500 + i * 5, None)
+        };
+        let points: Vec<_> = (0..10).map(dem_point).collect();
+        let stats = elevation_stats(&points);
+        assert_eq!(stats["elevation_source"], "dem");
+        // Constant pressure would give no gain
+        assert!(stats["elevation_up"].as_i64().unwrap() > 30);
+        // Less than 80% of the points with elevation model lookups
+        let points: Vec<_> = (0..10)
+            .map(|i| {
+                if i < 5 {
+                    dem_point(i)
+                } else {
+                    elevation_point(500 + i * 5, None)
+                }
+            })
+            .collect();
+        assert_eq!(elevation_stats(&points)["elevation_source"], "gps");
+        assert_eq!(elevation_stats(&[])["elevation_source"], "gps");
+    }
+
+    #[test]
+    fn elevation_downweights_inaccurate_altitudes() {
+        let mut points: Vec<_> = (0..11).map(|_| elevation_point(500, Some(5))).collect();
+        points[5] = elevation_point(540, Some(5));
+        assert!(elevation_stats(&points)["elevation_up"].as_i64().unwrap() >= 5);
+        points[5] = elevation_point(540, Some(100));
+        let stats = elevation_stats(&points);
+        assert_eq!(stats["elevation_up"], 0);
+        assert_eq!(stats["elevation_down"], 0);
+    }
+}
@@ -0,0 +1,239 @@
+use crate::activity::{self, ActivityType, Sample};
+use crate::db::TrackData;
+use chrono::{DateTime, SecondsFormat};
+use std::fmt::Write as _;
+
+fn sport(activity: ActivityType) -> &'static str {
+    match activity {
+        ActivityType::Running => "Running",
+        ActivityType::Cycling => "Biking",
+        _ => "Other",
+    }
+}
+
+fn time(ts: i64) -> String {
+    DateTime::from_timestamp(ts, 0)
+        .unwrap_or_default()
+        .to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+fn write_trackpoint(tcx: &mut String, sample: &Sample, distance: f64) -> std::fmt::Result {
+    writeln!(tcx, "          <Trackpoint>")?;
+    writeln!(tcx, "            <Time>{}</Time>", time(sample.ts))?;
+    writeln!(tcx, "            <Position>")?;
+    writeln!(
+        tcx,
+        "              <LatitudeDegrees>{}</LatitudeDegrees>",
+        sample.y
+    )?;
+    writeln!(
+        tcx,
+        "              <LongitudeDegrees>{}</LongitudeDegrees>",
+        sample.x
+    )?;
+    writeln!(tcx, "            </Position>")?;
+    if let Some(altitude) = sample.altitude {
+        writeln!(
+            tcx,
+            "            <AltitudeMeters>{altitude}</AltitudeMeters>"
+        )?;
+    }
+    writeln!(
+        tcx,
+        "            <DistanceMeters>{distance:.1}</DistanceMeters>"
+    )?;
+    if let Some(speed) = sample.speed {
+        writeln!(tcx, "            <Extensions>")?;
+        writeln!(tcx, "              <ns3:TPX>")?;
+        writeln!(tcx, "                <ns3:Speed>{speed:.2}</ns3:Speed>")?;
+        writeln!(tcx, "              </ns3:TPX>")?;
+        writeln!(tcx, "            </Extensions>")?;
+    }
+    writeln!(tcx, "          </Trackpoint>")
+}
+
+/// Build a TCX activity with one lap per track.
+pub fn tracks(tracks: &[TrackData], activity: ActivityType) -> anyhow::Result<String> {
+    let laps: Vec<Vec<Sample>> = tracks
+        .iter()
+        .map(activity::samples)
+        .filter(|samples| !samples.is_empty())
+        .collect();
+    let mut tcx = String::new();
+    writeln!(tcx, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        tcx,
+        r#"<TrainingCenterDatabase xmlns="http://www.garmin.com/xmlschemas/TrainingCenterDatabase/v2" xmlns:ns3="http://www.garmin.com/xmlschemas/ActivityExtension/v2" xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance" xsi:schemaLocation="http://www.garmin.com/xmlschemas/TrainingCenterDatabase/v2 http://www.garmin.com/xmlschemas/TrainingCenterDatabasev2.xsd">"#
+    )?;
+    writeln!(tcx, "  <Activities>")?;
+    if let Some(first) = laps.first().map(|samples| &samples[0]) {
+        writeln!(tcx, r#"    <Activity Sport="{}">"#, sport(activity))?;
+        writeln!(tcx, "      <Id>{}</Id>", time(first.ts))?;
+        // Distances are cumulated over all laps
+        let mut distance_offset = 0.0;
+        for samples in &laps {
+            let (start, end) = (&samples[0], &samples[samples.len() - 1]);
+            writeln!(tcx, r#"      <Lap StartTime="{}">"#, time(start.ts))?;
+            writeln!(
+                tcx,
+                "        <TotalTimeSeconds>{}</TotalTimeSeconds>",
+                end.ts - start.ts
+            )?;
+            writeln!(
+                tcx,
+                "        <DistanceMeters>{:.1}</DistanceMeters>",
+                end.distance
+            )?;
+            if let Some(max_speed) = samples
+                .iter()
+                .filter_map(|sample| sample.speed)
+                .reduce(f64::max)
+            {
+                writeln!(tcx, "        <MaximumSpeed>{max_speed:.2}</MaximumSpeed>")?;
+            }
+            writeln!(tcx, "        <Calories>0</Calories>")?;
+            writeln!(tcx, "        <Intensity>Active</Intensity>")?;
+            writeln!(tcx, "        <TriggerMethod>Manual</TriggerMethod>")?;
+            writeln!(tcx, "        <Track>")?;
+            for sample in samples {
+                write_trackpoint(&mut tcx, sample, distance_offset + sample.distance)?;
+            }
+            writeln!(tcx, "        </Track>")?;
+            writeln!(tcx, "      </Lap>")?;
+            distance_offset += end.distance;
+        }
+        writeln!(tcx, "      <Creator xsi:type=\"Device_t\">")?;
+        writeln!(tcx, "        <Name>owntrack-rs</Name>")?;
+        writeln!(tcx, "        <UnitId>0</UnitId>")?;
+        writeln!(tcx, "        <ProductID>0</ProductID>")?;
+        writeln!(tcx, "        <Version>")?;
+        writeln!(
+            tcx,
+            "          <VersionMajor>{}</VersionMajor>",
+            env!("CARGO_PKG_VERSION_MAJOR")
+        )?;
+        writeln!(
+            tcx,
+            "          <VersionMinor>{}</VersionMinor>",
+            env!("CARGO_PKG_VERSION_MINOR")
+        )?;
+        writeln!(tcx, "        </Version>")?;
+        writeln!(tcx, "      </Creator>")?;
+        writeln!(tcx, "    </Activity>")?;
+    }
+    writeln!(tcx, "  </Activities>")?;
+    writeln!(tcx, "</TrainingCenterDatabase>")?;
+    Ok(tcx)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::GpsPoint;
+
+    fn track(date: &str, x0: f64) -> TrackData {
+        let point = |x: f64, time: &str| GpsPoint {
+            x,
+            y: 0.0,
+            ts: format!("{date} {time}"),
+            elevation: Some(500),
+            speed: Some(18),
+            ..Default::default()
+        };
+        TrackData {
+            device_id: 1,
+            date: date.to_string(),
+            points: vec![point(x0, "08:00:00"), point(x0 + 0.009, "08:10:00")],
+        }
+    }
+
+    /// Position of each element in the document, panics if missing
+    fn positions(tcx: &str, elements: &[&str]) -> Vec<usize> {
+        elements
+            .iter()
+            .map(|element| tcx.find(element).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn sports() {
+        assert_eq!(sport(ActivityType::Running), "Running");
+        assert_eq!(sport(ActivityType::Cycling), "Biking");
+        assert_eq!(sport(ActivityType::Hiking), "Other");
+        assert_eq!(sport(ActivityType::Walking), "Other");
+        assert_eq!(sport(ActivityType::Other), "Other");
+        let tcx = tracks(&[track("2025-10-09", 0.0)], ActivityType::Hiking).unwrap();
+        assert!(tcx.contains(r#"<Activity Sport="Other">"#));
+    }
+
+    #[test]
+    fn cumulated_lap_distances() {
+        let tcx = tracks(
+            &[track("2025-10-09", 0.0), track("2025-10-10", 1.0)],
+            ActivityType::Running,
+        )
+        .unwrap();
+        assert_eq!(tcx.matches("<Lap ").count(), 2);
+        // Lap and trackpoint distances by indentation
+        let distances = |indent: usize| {
+            let prefix = format!("{}<DistanceMeters>", " ".repeat(indent));
+            tcx.lines()
+                .filter(|line| line.starts_with(&prefix))
+                .map(str::trim)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(distances(8), ["<DistanceMeters>1000.8</DistanceMeters>"; 2]);
+        assert_eq!(
+            distances(12),
+            [
+                "<DistanceMeters>0.0</DistanceMeters>",
+                "<DistanceMeters>1000.8</DistanceMeters>",
+                "<DistanceMeters>1000.8</DistanceMeters>",
+                "<DistanceMeters>2001.5</DistanceMeters>",
+            ]
+        );
+        assert!(tracks(&[], ActivityType::Running)
+            .unwrap()
+            .contains("<Activities>\n  </Activities>"));
+    }
+
+    #[test]
+    fn element_order() {
+        let tcx = tracks(&[track("2025-10-09", 0.0)], ActivityType::Cycling).unwrap();
+        let activity = positions(
+            &tcx,
+            &[
+                "<Activity ",
+                "<Id>2025-10-09T08:00:00Z</Id>",
+                "<Lap StartTime=\"2025-10-09T08:00:00Z\">",
+                "<TotalTimeSeconds>600</TotalTimeSeconds>",
+                "<DistanceMeters>",
+                "<MaximumSpeed>5.00</MaximumSpeed>",
+                "<Calories>",
+                "<Intensity>",
+                "<TriggerMethod>",
+                "<Track>",
+                "</Lap>",
+                "<Creator ",
+                "</Activity>",
+            ],
+        );
+        assert!(activity.is_sorted());
+        let trackpoint = &tcx[tcx.find("<Trackpoint>").unwrap()..];
+        let trackpoint = positions(
+            trackpoint,
+            &[
+                "<Time>",
+                "<Position>",
+                "<LatitudeDegrees>",
+                "<LongitudeDegrees>",
+                "</Position>",
+                "<AltitudeMeters>",
+                "<DistanceMeters>",
+                "<Extensions>",
+                "</Trackpoint>",
+            ],
+        );
+        assert!(trackpoint.is_sorted());
+    }
+}
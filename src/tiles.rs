@@ -0,0 +1,138 @@
+use crate::db::{day_range, AreaFilter, Db};
+use crate::mvt::{self, Layer, TileCoord, Value};
+use crate::simplify::{self, Simplification};
+use serde::Deserialize;
+
+/// Minimal zoom level of the points layer
+const POINTS_MIN_ZOOM: u8 = 12;
+/// Buffer around tiles in tile units
+const TILE_BUFFER: f64 = 1.0 / 16.0;
+/// Maximal number of days rendered into a tile
+const MAX_DAYS: i64 = 31;
+
+#[derive(Deserialize, Debug)]
+pub struct TileParams {
+    pub device_id: Option<i32>,
+    /// First date in format 2025-02-19
+    pub date_from: String,
+    /// Last date in format 2025-02-19 (default: `date_from`)
+    pub date_to: Option<String>,
+}
+
+impl TileParams {
+    /// First and last date, at most `MAX_DAYS` days
+    pub fn dates(&self) -> anyhow::Result<(String, String)> {
+        let date_to = self.date_to.as_ref().unwrap_or(&self.date_from);
+        let (Some((start, _)), Some((_, end))) = (day_range(&self.date_from), day_range(date_to))
+        else {
+            anyhow::bail!("invalid date");
+        };
+        if start >= end {
+            anyhow::bail!("`date_to` before `date_from`");
+        }
+        if end - start > MAX_DAYS * 24 * 3600 {
+            anyhow::bail!("date range longer than {MAX_DAYS} days");
+        }
+        Ok((self.date_from.clone(), date_to.clone()))
+    }
+
+    /// Check the date range
+    pub fn validate(&self) -> anyhow::Result<()> {
+        self.dates().map(|_| ())
+    }
+
+    /// Tracks of past days don't change anymore
+    pub fn is_past(&self) -> bool {
+        let today = chrono::Utc::now().date_naive().format("%F").to_string();
+        self.date_to.as_ref().unwrap_or(&self.date_from) < &today
+    }
+}
+
+/// Render tracks of a date range into a vector tile with the layers `tracks` and `points`.
+///
+/// Tracks are simplified with a tolerance of one pixel at the tile zoom level.
+/// Points are included from zoom level 12.
+pub async fn track_tile(db: &Db, tile: TileCoord, params: &TileParams) -> anyhow::Result<Vec<u8>> {
+    let (date_from, date_to) = params.dates()?;
+    let filter = AreaFilter {
+        bbox: tile.bbox(TILE_BUFFER),
+        date_from: Some(date_from),
+        date_to: Some(date_to),
+        device_id: params.device_id,
+        area: None,
+    };
+    let tracks = db.query_area_tracks(&filter).await?;
+
+    let mut lines = Layer::new("tracks");
+    let mut points = Layer::new("points");
+    let buffer = TILE_BUFFER * mvt::EXTENT as f64;
+    let (min, max) = (-buffer, mvt::EXTENT as f64 + buffer);
+    let round = |(x, y): (f64, f64)| (x.round() as i32, y.round() as i32);
+    for track in tracks {
+        let device_id = track.device_id;
+        for fixes in track.runs {
+            if tile.z >= POINTS_MIN_ZOOM {
+                for point in &fixes {
+                    let (x, y) = tile.project(point.x, point.y);
+                    if x < min || x > max || y < min || y > max {
+                        continue;
+                    }
+                    let mut properties = vec![
+                        ("device_id", Value::Int(device_id as i64)),
+                        ("ts", Value::String(point.ts.clone())),
+                    ];
+                    if let Some(speed) = point.speed {
+                        properties.push(("speed", Value::Int(speed as i64)));
+                    }
+                    if let Some(elevation) = point.elevation {
+                        properties.push(("elevation", Value::Int(elevation as i64)));
+                    }
+                    points.add_point(round((x, y)), properties);
+                }
+            }
+
+            let simplified = simplify::simplify(fixes, Simplification::Dp, None, Some(tile.z));
+            let line: Vec<(f64, f64)> = simplified
+                .iter()
+                .map(|point| tile.project(point.x, point.y))
+                .collect();
+            for part in mvt::clip_line(&line, min, max) {
+                let part: Vec<(i32, i32)> = part.into_iter().map(round).collect();
+                lines.add_line(
+                    &part,
+                    vec![
+                        ("device_id", Value::Int(device_id as i64)),
+                        ("date", Value::String(track.date.clone())),
+                    ],
+                );
+            }
+        }
+    }
+    Ok(mvt::encode_tile(&[lines, points]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(date_from: &str, date_to: Option<&str>) -> TileParams {
+        TileParams {
+            device_id: None,
+            date_from: date_from.to_string(),
+            date_to: date_to.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn date_range() {
+        assert_eq!(
+            params("2025-10-09", None).dates().unwrap(),
+            ("2025-10-09".to_string(), "2025-10-09".to_string())
+        );
+        assert!(params("2025-10-01", Some("2025-10-31")).validate().is_ok());
+        assert!(params("2025-10-01", Some("2025-11-01")).validate().is_err());
+        assert!(params("2025-10-09", Some("2025-10-08")).validate().is_err());
+        assert!(params("2025-13-09", None).validate().is_err());
+        assert!(params("2025-10-09", None).is_past());
+    }
+}